@@ -8,12 +8,16 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+extern crate chrono;
 extern crate edn;
 extern crate ordered_float;
+extern crate uuid;
 
 use std::collections::BTreeMap;
+use self::chrono::{DateTime, TimeZone, Timelike, Utc};
 use self::ordered_float::OrderedFloat;
 use self::edn::NamespacedKeyword;
+use self::uuid::Uuid;
 
 /// Core types defining a Mentat knowledge base.
 
@@ -35,10 +39,12 @@ pub enum ValueType {
     Double,
     String,
     Keyword,
+    Uuid,
+    Uri,
+    Bytes,
 }
 
 /// Represents a Mentat value in a particular value set.
-// TODO: expand to include :db.type/{instant,url,uuid}.
 // TODO: BigInt?
 #[derive(Clone,Debug,Eq,Hash,Ord,PartialOrd,PartialEq)]
 pub enum TypedValue {
@@ -46,9 +52,15 @@ pub enum TypedValue {
     Boolean(bool),
     Long(i64),
     Double(OrderedFloat<f64>),
+    /// Instants are stored with microsecond precision; see `FromMicros` and `ToMicros`.
+    Instant(DateTime<Utc>),
     // TODO: &str throughout?
     String(String),
     Keyword(NamespacedKeyword),
+    Uuid(Uuid),
+    /// URIs are not yet validated or normalized.
+    Uri(String),
+    Bytes(Vec<u8>),
 }
 
 impl TypedValue {
@@ -70,11 +82,51 @@ impl TypedValue {
             &TypedValue::Boolean(_) => ValueType::Boolean,
             &TypedValue::Long(_) => ValueType::Long,
             &TypedValue::Double(_) => ValueType::Double,
+            &TypedValue::Instant(_) => ValueType::Instant,
             &TypedValue::String(_) => ValueType::String,
             &TypedValue::Keyword(_) => ValueType::Keyword,
+            &TypedValue::Uuid(_) => ValueType::Uuid,
+            &TypedValue::Uri(_) => ValueType::Uri,
+            &TypedValue::Bytes(_) => ValueType::Bytes,
         }
     }
 
+    /// Construct an instant value, truncated to the microsecond precision that we store.
+    pub fn instant(t: DateTime<Utc>) -> TypedValue {
+        TypedValue::Instant(truncate_to_micros(t))
+    }
+}
+
+/// Convert from a count of microseconds after the Unix epoch, which is how we store instants.
+/// Returns `None` if the count is out of the representable range.
+pub trait FromMicros: Sized {
+    fn from_micros(ts: i64) -> Option<Self>;
+}
+
+impl FromMicros for DateTime<Utc> {
+    fn from_micros(ts: i64) -> Option<Self> {
+        // Floor, so that instants before the epoch have a non-negative sub-second part.
+        let seconds = ts.div_euclid(1_000_000);
+        let micros = ts.rem_euclid(1_000_000);
+        Utc.timestamp_opt(seconds, (micros * 1_000) as u32).single()
+    }
+}
+
+/// Truncate an instant to the microsecond precision that we store.
+pub fn truncate_to_micros(t: DateTime<Utc>) -> DateTime<Utc> {
+    // Dropping nanoseconds can't take the sub-second part out of range, so this always succeeds.
+    t.with_nanosecond(t.nanosecond() / 1_000 * 1_000).unwrap_or(t)
+}
+
+/// Convert to a count of microseconds after the Unix epoch, truncating any finer precision.
+pub trait ToMicros {
+    fn to_micros(&self) -> i64;
+}
+
+impl ToMicros for DateTime<Utc> {
+    fn to_micros(&self) -> i64 {
+        self.timestamp() * 1_000_000 + (self.nanosecond() / 1_000) as i64
+    }
 }
 
 // Put this here rather than in `db` simply because it's widely needed.
@@ -88,11 +140,14 @@ impl SQLValueType for ValueType {
         match *self {
             ValueType::Ref =>      0,
             ValueType::Boolean =>  1,
+            ValueType::Bytes =>    2,
             ValueType::Instant =>  4,
             // SQLite distinguishes integral from decimal types, allowing long and double to share a tag.
             ValueType::Long =>     5,
             ValueType::Double =>   5,
             ValueType::String =>  10,
+            ValueType::Uuid =>    11,
+            ValueType::Uri =>     12,
             ValueType::Keyword => 13,
         }
     }
@@ -115,6 +170,9 @@ impl SQLValueType for ValueType {
             Boolean                 => (int == 0) || (int == 1),
             ValueType::String       => false,
             Keyword                 => false,
+            ValueType::Uuid         => false,
+            Uri                     => false,
+            Bytes                   => false,
         }
     }
 }
@@ -126,6 +184,31 @@ fn test_typed_value() {
     assert!(!TypedValue::String("foo".to_string()).is_congruent_with(ValueType::Boolean));
     assert!(TypedValue::String("foo".to_string()).is_congruent_with(ValueType::String));
     assert!(TypedValue::String("foo".to_string()).is_congruent_with(None));
+    assert!(TypedValue::instant(Utc.timestamp(0, 0)).is_congruent_with(ValueType::Instant));
+    assert!(TypedValue::Uuid(Uuid::nil()).is_congruent_with(ValueType::Uuid));
+    assert!(!TypedValue::Uri("https://example.com/".to_string()).is_congruent_with(ValueType::String));
+    assert!(TypedValue::Bytes(vec![0xde, 0xad]).is_congruent_with(ValueType::Bytes));
+}
+
+#[test]
+fn test_micros_roundtrip() {
+    let t = Utc.ymd(2017, 4, 28).and_hms_micro(20, 23, 5, 187_123);
+    assert_eq!(t.to_micros(), 1_493_410_985_187_123);
+    assert_eq!(DateTime::<Utc>::from_micros(t.to_micros()), Some(t));
+
+    // Before the epoch.
+    let t = Utc.ymd(1969, 12, 31).and_hms_micro(23, 59, 59, 999_999);
+    assert_eq!(t.to_micros(), -1);
+    assert_eq!(DateTime::<Utc>::from_micros(-1), Some(t));
+    assert_eq!(DateTime::<Utc>::from_micros(-1_000_000), Some(Utc.timestamp(-1, 0)));
+
+    // Out of range.
+    assert_eq!(DateTime::<Utc>::from_micros(::std::i64::MAX), None);
+    assert_eq!(DateTime::<Utc>::from_micros(::std::i64::MIN), None);
+
+    // Finer precision is truncated.
+    let t = Utc.ymd(2017, 4, 28).and_hms_nano(20, 23, 5, 187_123_456);
+    assert_eq!(TypedValue::instant(t), TypedValue::Instant(Utc.ymd(2017, 4, 28).and_hms_micro(20, 23, 5, 187_123)));
 }
 
 /// Bit flags used in `flags0` column in temporary tables created during search,
@@ -137,6 +220,7 @@ pub enum AttributeBitFlags {
     IndexVAET     = 1 << 1,
     IndexFulltext = 1 << 2,
     UniqueValue   = 1 << 3,
+    NoHistory     = 1 << 4,
 }
 
 /// A Mentat schema attribute has a value type and several other flags determining how assertions
@@ -182,6 +266,18 @@ pub struct Attribute {
     /// They are used to compose entities from component sub-entities: they are fetched recursively
     /// by pull expressions, and they are automatically recursively deleted where appropriate.
     pub component: bool,
+
+    /// `true` if this attribute doesn't retain history, i.e., it is `:db/noHistory true`.
+    ///
+    /// Retracting a datom with such an attribute removes its assertion from the transaction log
+    /// rather than recording the retraction.
+    pub no_history: bool,
+
+    /// `true` if this `:db.type/bytes` attribute may be indexed, i.e., it is `:db/indexBytes true`.
+    ///
+    /// Indexing arbitrary binaries is expensive, so bytes attributes must opt in to `:db/index`
+    /// and `:db/unique`.
+    pub index_bytes: bool,
 }
 
 impl Attribute {
@@ -201,6 +297,9 @@ impl Attribute {
         if self.unique_value {
             flags |= AttributeBitFlags::UniqueValue as u8;
         }
+        if self.no_history {
+            flags |= AttributeBitFlags::NoHistory as u8;
+        }
         flags
     }
 }
@@ -216,6 +315,8 @@ impl Default for Attribute {
             unique_value: false,
             unique_identity: false,
             component: false,
+            no_history: false,
+            index_bytes: false,
         }
     }
 }
@@ -298,6 +399,8 @@ mod test {
             multival: false,
             unique_identity: false,
             component: false,
+            no_history: false,
+            index_bytes: false,
         };
 
         assert!(attr1.flags() & AttributeBitFlags::IndexAVET as u8 != 0);
@@ -313,12 +416,15 @@ mod test {
             multival: false,
             unique_identity: false,
             component: false,
+            no_history: true,
+            index_bytes: false,
         };
 
         assert!(attr2.flags() & AttributeBitFlags::IndexAVET as u8 == 0);
         assert!(attr2.flags() & AttributeBitFlags::IndexVAET as u8 == 0);
         assert!(attr2.flags() & AttributeBitFlags::IndexFulltext as u8 != 0);
         assert!(attr2.flags() & AttributeBitFlags::UniqueValue as u8 != 0);
+        assert!(attr2.flags() & AttributeBitFlags::NoHistory as u8 != 0);
     }
 }
 
@@ -79,6 +79,25 @@ lazy_static! {
          ]].concat()
     };
 
+    static ref V5_IDENTS: Vec<(symbols::NamespacedKeyword, i64)> = {
+        [(*V2_IDENTS).clone(),
+         vec![(ns_keyword!("db.revert", "tx"),        entids::DB_REVERT_TX),
+         ]].concat()
+    };
+
+    static ref V7_IDENTS: Vec<(symbols::NamespacedKeyword, i64)> = {
+        [(*V5_IDENTS).clone(),
+         vec![(ns_keyword!("db.type", "uuid"),        entids::DB_TYPE_UUID),
+              (ns_keyword!("db.type", "uri"),         entids::DB_TYPE_URI),
+         ]].concat()
+    };
+
+    static ref V8_IDENTS: Vec<(symbols::NamespacedKeyword, i64)> = {
+        [(*V7_IDENTS).clone(),
+         vec![(ns_keyword!("db", "indexBytes"),       entids::DB_INDEX_BYTES),
+         ]].concat()
+    };
+
     static ref V1_PARTS: Vec<(symbols::NamespacedKeyword, i64, i64)> = {
         vec![(ns_keyword!("db.part", "db"), 0, (1 + V1_IDENTS.len()) as i64),
              (ns_keyword!("db.part", "user"), 0x10000, 0x10000),
@@ -153,6 +172,72 @@ lazy_static! {
             .ok_or(ErrorKind::BadBootstrapDefinition("Unable to parse V2_SYMBOLIC_SCHEMA".into()))
             .unwrap()
     };
+
+    static ref V4_SYMBOLIC_SCHEMA: Value = {
+        let s = r#"
+{:db/excise            {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/one}
+ :db.excise/attrs      {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/many}
+ :db.excise/beforeT    {:db/valueType   :db.type/long
+                        :db/cardinality :db.cardinality/one}
+ :db.excise/before     {:db/valueType   :db.type/long
+                        :db/cardinality :db.cardinality/one}}"#;
+        let right = edn::parse::value(s)
+            .map(|v| v.without_spans())
+            .map_err(|_| ErrorKind::BadBootstrapDefinition("Unable to parse V4_SYMBOLIC_SCHEMA".into()))
+            .unwrap();
+
+        edn::utils::merge(&V2_SYMBOLIC_SCHEMA, &right)
+            .ok_or(ErrorKind::BadBootstrapDefinition("Unable to parse V4_SYMBOLIC_SCHEMA".into()))
+            .unwrap()
+    };
+
+    static ref V5_SYMBOLIC_SCHEMA: Value = {
+        let s = r#"
+{:db.revert/tx         {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/one}}"#;
+        let right = edn::parse::value(s)
+            .map(|v| v.without_spans())
+            .map_err(|_| ErrorKind::BadBootstrapDefinition("Unable to parse V5_SYMBOLIC_SCHEMA".into()))
+            .unwrap();
+
+        edn::utils::merge(&V4_SYMBOLIC_SCHEMA, &right)
+            .ok_or(ErrorKind::BadBootstrapDefinition("Unable to parse V5_SYMBOLIC_SCHEMA".into()))
+            .unwrap()
+    };
+
+    static ref V6_SYMBOLIC_SCHEMA: Value = {
+        // `merge` replaces whole attribute maps, so restate every property of the retyped attributes.
+        let s = r#"
+{:db/txInstant         {:db/valueType   :db.type/instant
+                        :db/cardinality :db.cardinality/one
+                        :db/index       true}
+ :db.excise/before     {:db/valueType   :db.type/instant
+                        :db/cardinality :db.cardinality/one}}"#;
+        let right = edn::parse::value(s)
+            .map(|v| v.without_spans())
+            .map_err(|_| ErrorKind::BadBootstrapDefinition("Unable to parse V6_SYMBOLIC_SCHEMA".into()))
+            .unwrap();
+
+        edn::utils::merge(&V5_SYMBOLIC_SCHEMA, &right)
+            .ok_or(ErrorKind::BadBootstrapDefinition("Unable to parse V6_SYMBOLIC_SCHEMA".into()))
+            .unwrap()
+    };
+
+    static ref V8_SYMBOLIC_SCHEMA: Value = {
+        let s = r#"
+{:db/indexBytes        {:db/valueType   :db.type/boolean
+                        :db/cardinality :db.cardinality/one}}"#;
+        let right = edn::parse::value(s)
+            .map(|v| v.without_spans())
+            .map_err(|_| ErrorKind::BadBootstrapDefinition("Unable to parse V8_SYMBOLIC_SCHEMA".into()))
+            .unwrap();
+
+        edn::utils::merge(&V6_SYMBOLIC_SCHEMA, &right)
+            .ok_or(ErrorKind::BadBootstrapDefinition("Unable to parse V8_SYMBOLIC_SCHEMA".into()))
+            .unwrap()
+    };
 }
 
 /// Convert (ident, entid) pairs into [:db/add IDENT :db/ident IDENT] `Value` instances.
@@ -257,21 +342,41 @@ pub fn bootstrap_partition_map() -> PartitionMap {
 }
 
 pub fn bootstrap_ident_map() -> IdentMap {
-    V2_IDENTS[..].iter()
+    V8_IDENTS[..].iter()
         .map(|&(ref ident, entid)| (ident.clone(), entid))
         .collect()
 }
 
-pub fn bootstrap_schema() -> Schema {
-    let ident_map = bootstrap_ident_map();
-    let bootstrap_triples = symbolic_schema_to_triples(&ident_map, &V2_SYMBOLIC_SCHEMA).unwrap();
+/// The bootstrap idents and symbolic schema that define the given store version.
+fn bootstrap_definition(version: i32) -> (&'static [(symbols::NamespacedKeyword, i64)], &'static Value) {
+    match version {
+        1 => (&V1_IDENTS[..], &*V1_SYMBOLIC_SCHEMA),
+        // Version 3 only changed the SQL schema.
+        2 | 3 => (&V2_IDENTS[..], &*V2_SYMBOLIC_SCHEMA),
+        4 => (&V2_IDENTS[..], &*V4_SYMBOLIC_SCHEMA),
+        5 => (&V5_IDENTS[..], &*V5_SYMBOLIC_SCHEMA),
+        // Version 6 retyped :db/txInstant and :db.excise/before, but assigned no new idents.
+        6 => (&V5_IDENTS[..], &*V6_SYMBOLIC_SCHEMA),
+        // Version 7 added value types, which have idents but no schema.
+        7 => (&V7_IDENTS[..], &*V6_SYMBOLIC_SCHEMA),
+        8 => (&V8_IDENTS[..], &*V8_SYMBOLIC_SCHEMA),
+        // This is a programming error.
+        _ => panic!("No bootstrap definition for store version {}", version),
+    }
+}
+
+fn schema_for(idents: &[(symbols::NamespacedKeyword, i64)], symbolic_schema: &Value) -> Schema {
+    let ident_map: IdentMap = idents.iter()
+        .map(|&(ref ident, entid)| (ident.clone(), entid))
+        .collect();
+    let bootstrap_triples = symbolic_schema_to_triples(&ident_map, symbolic_schema).unwrap();
     Schema::from_ident_map_and_triples(ident_map, bootstrap_triples).unwrap()
 }
 
-pub fn bootstrap_entities() -> Vec<Entity> {
+fn entities_for(idents: &[(symbols::NamespacedKeyword, i64)], symbolic_schema: &Value) -> Vec<Entity> {
     let bootstrap_assertions: Value = Value::Vector([
-        symbolic_schema_to_assertions(&V2_SYMBOLIC_SCHEMA).unwrap(),
-        idents_to_assertions(&V2_IDENTS[..]),
+        symbolic_schema_to_assertions(symbolic_schema).unwrap(),
+        idents_to_assertions(idents),
     ].concat());
 
     // Failure here is a coding error (since the inputs are fixed), not a runtime error.
@@ -279,3 +384,32 @@ pub fn bootstrap_entities() -> Vec<Entity> {
     let bootstrap_entities: Vec<Entity> = mentat_tx_parser::Tx::parse(&[bootstrap_assertions][..]).unwrap();
     return bootstrap_entities;
 }
+
+pub fn bootstrap_schema() -> Schema {
+    schema_for(&V8_IDENTS[..], &V8_SYMBOLIC_SCHEMA)
+}
+
+pub fn bootstrap_entities() -> Vec<Entity> {
+    entities_for(&V8_IDENTS[..], &V8_SYMBOLIC_SCHEMA)
+}
+
+/// The bootstrap schema of the given store version, which migrations to that version transact
+/// against.
+pub fn bootstrap_schema_for_version(version: i32) -> Schema {
+    let (idents, symbolic_schema) = bootstrap_definition(version);
+    schema_for(idents, symbolic_schema)
+}
+
+/// The bootstrap idents of the given store version.
+pub fn bootstrap_ident_map_for_version(version: i32) -> IdentMap {
+    let (idents, _) = bootstrap_definition(version);
+    idents.iter()
+        .map(|&(ref ident, entid)| (ident.clone(), entid))
+        .collect()
+}
+
+/// The bootstrap entities of the given store version, which migrations to that version transact.
+pub fn bootstrap_entities_for_version(version: i32) -> Vec<Entity> {
+    let (idents, symbolic_schema) = bootstrap_definition(version);
+    entities_for(idents, symbolic_schema)
+}
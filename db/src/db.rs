@@ -11,39 +11,53 @@
 #![allow(dead_code)]
 
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{
+    BTreeSet,
+    HashMap,
+    HashSet,
+};
 use std::fmt::Display;
 use std::iter::{once, repeat};
 use std::ops::Range;
 use std::path::Path;
 
+use chrono::{DateTime, Utc};
 use itertools;
 use itertools::Itertools;
 use rusqlite;
 use rusqlite::types::{ToSql, ToSqlOutput};
 use rusqlite::limits::Limit;
+use uuid::Uuid;
 
 use ::{repeat_values, to_namespaced_keyword};
 use bootstrap;
+use entids;
 use edn::types::Value;
 use edn::symbols;
 use mentat_core::{
     Attribute,
     AttributeBitFlags,
     Entid,
+    FromMicros,
     IdentMap,
     Schema,
+    SQLValueType,
+    ToMicros,
     TypedValue,
     ValueType,
 };
 use errors::{ErrorKind, Result, ResultExt};
+use mentat_tx::entities as entmod;
+use mentat_tx::entities::{Entity, OpType};
 use schema::SchemaBuilding;
 use types::{
     AVMap,
     AVPair,
+    Datom,
     DB,
     Partition,
     PartitionMap,
+    TxLogEntry,
 };
 use tx::transact;
 
@@ -59,6 +73,7 @@ pub fn new_connection<T>(uri: T) -> rusqlite::Result<rusqlite::Connection> where
         PRAGMA wal_autocheckpoint=32;
         PRAGMA journal_size_limit=3145728;
         PRAGMA foreign_keys=ON;
+        PRAGMA busy_timeout=5000;
     ")?;
 
     Ok(conn)
@@ -69,7 +84,17 @@ pub fn new_connection<T>(uri: T) -> rusqlite::Result<rusqlite::Connection> where
 /// 1: initial schema.
 /// 2: added :db.schema/version and /attribute in bootstrap; assigned idents 36 and 37, so we bump
 ///    the part range here; tie bootstrapping to the SQLite user_version.
-pub const CURRENT_VERSION: i32 = 2;
+/// 3: indexed transactions by (e, a, value_type_tag, v), for as-of queries.
+/// 4: added :db/excise, :db.excise/attrs, :db.excise/beforeT and :db.excise/before to the bootstrap
+///    schema.  The idents were already assigned in version 1.  Indexed the fulltext values of
+///    transactions, so that excision can garbage collect fulltext values without scanning the log.
+/// 5: added :db.revert/tx in bootstrap, with an entid from the reserved end of :db.part/db.
+/// 6: :db/txInstant and :db.excise/before became :db.type/instant; existing millisecond longs are
+///    rewritten as microsecond instants.
+/// 7: added :db.type/uuid and :db.type/uri in bootstrap, with entids from the reserved end of
+///    :db.part/db.
+/// 8: added :db/indexBytes in bootstrap, with an entid from the reserved end of :db.part/db.
+pub const CURRENT_VERSION: i32 = 8;
 
 const TRUE: &'static bool = &true;
 const FALSE: &'static bool = &false;
@@ -83,7 +108,7 @@ fn to_bool_ref(x: bool) -> &'static bool {
 }
 
 lazy_static! {
-    /// SQL statements to be executed, in order, to create the Mentat SQL schema (version 2).
+    /// SQL statements to be executed, in order, to create the Mentat SQL schema (version 3).
     #[cfg_attr(rustfmt, rustfmt_skip)]
     static ref V2_STATEMENTS: Vec<&'static str> = { vec![
         r#"CREATE TABLE datoms (e INTEGER NOT NULL, a SMALLINT NOT NULL, v BLOB NOT NULL, tx INTEGER NOT NULL,
@@ -114,6 +139,10 @@ lazy_static! {
 
         r#"CREATE TABLE transactions (e INTEGER NOT NULL, a SMALLINT NOT NULL, v BLOB NOT NULL, tx INTEGER NOT NULL, added TINYINT NOT NULL DEFAULT 1, value_type_tag SMALLINT NOT NULL)"#,
         r#"CREATE INDEX idx_transactions_tx ON transactions (tx, added)"#,
+        // As-of queries look for later transactions of the same datom.
+        r#"CREATE INDEX idx_transactions_eavt ON transactions (e, a, value_type_tag, v)"#,
+        // Garbage collecting fulltext values looks for transactions that reference them.
+        r#"CREATE INDEX idx_transactions_fulltext ON transactions (v) WHERE value_type_tag = 10 AND typeof(v) = 'integer'"#,
 
         // Fulltext indexing.
         // A fulltext indexed value v is an integer rowid referencing fulltext_values.
@@ -210,11 +239,17 @@ pub fn create_current_version(conn: &mut rusqlite::Connection) -> Result<DB> {
     // TODO: return to transact_internal to self-manage the encompassing SQLite transaction.
     let bootstrap_schema = bootstrap::bootstrap_schema();
     let (_report, next_partition_map, next_schema) = transact(&tx, bootstrap_partition_map, &bootstrap_schema, bootstrap::bootstrap_entities())?;
-    if next_schema.is_some() {
+    // The bootstrap transaction installs the bootstrap attributes, so it always produces a schema;
+    // that schema must be the bootstrap schema itself.
+    if next_schema.as_ref() != Some(&bootstrap_schema) {
         // TODO Use custom ErrorKind https://github.com/brson/error-chain/issues/117
-        bail!(ErrorKind::NotYetImplemented(format!("Initial bootstrap transaction did not produce expected bootstrap schema")));
+        bail!(ErrorKind::NotYetImplemented("Initial bootstrap transaction did not produce expected bootstrap schema".to_string()));
     }
 
+    // The bootstrap transaction only writes datoms; materialize the idents and schema so that the
+    // store can be re-opened with `read_db`.
+    rebuild_idents_and_schema(&tx)?;
+
     set_user_version(&tx, CURRENT_VERSION)?;
 
     // TODO: use the drop semantics to do this automagically?
@@ -308,27 +343,192 @@ pub fn create_current_version(conn: &mut rusqlite::Connection) -> Result<DB> {
 //         (<? (<update-from-version db v bootstrapper))))))
 // */
 
-pub fn update_from_version(conn: &mut rusqlite::Connection, current_version: i32) -> Result<i32> {
-    if current_version < 0 || CURRENT_VERSION <= current_version {
+/// A single migration step, taking the SQL store from `from_version` to `from_version + 1`.
+///
+/// A step first executes its SQL `statements`, in order, and then transacts the bootstrap entities
+/// of version `from_version + 1` against that version's bootstrap schema.
+struct Migration {
+    from_version: i32,
+    statements: Vec<String>,
+}
+
+impl Migration {
+    fn to_version(&self) -> i32 {
+        self.from_version + 1
+    }
+
+    /// The entids that this step assigns to new bootstrap idents.
+    fn new_entids(&self) -> Vec<Entid> {
+        let existing: HashSet<Entid> = bootstrap::bootstrap_ident_map_for_version(self.from_version).values().cloned().collect();
+        let mut new_entids: Vec<Entid> = bootstrap::bootstrap_ident_map_for_version(self.to_version()).values()
+            .filter(|entid| !existing.contains(entid))
+            .cloned()
+            .collect();
+        new_entids.sort();
+        new_entids
+    }
+
+    /// Fail if the store has already allocated or used any of the entids that this step assigns to
+    /// new bootstrap idents, rather than silently merge the bootstrap idents into existing entities.
+    /// New bootstrap idents are assigned reserved entids that transactions can't allocate, so this
+    /// only fails if a transaction named a reserved entid explicitly.
+    fn ensure_new_entids_unused(&self, conn: &rusqlite::Connection) -> Result<()> {
+        let new_entids = self.new_entids();
+        if new_entids.is_empty() {
+            return Ok(());
+        }
+
+        let partition_map = read_partition_map(conn)?;
+        let next_entid = partition_map.get(":db.part/db")
+            .map(|partition| partition.index)
+            .ok_or_else(|| ErrorKind::UnrecognizedPartition(":db.part/db".to_string()))?;
+
+        let s = format!(r#"
+          SELECT e FROM datoms WHERE e IN ({entids})
+          UNION
+          SELECT e FROM transactions WHERE e IN ({entids})"#,
+          entids = new_entids.iter().join(", "));
+        let mut stmt = conn.prepare(&s)?;
+        let used: Result<HashSet<Entid>> = stmt.query_and_then(&[], |row| Ok(row.get_checked(0)?))?.collect();
+        let used = used?;
+
+        let in_use: Vec<Entid> = new_entids.into_iter()
+            .filter(|entid| *entid < next_entid || used.contains(entid))
+            .collect();
+        if !in_use.is_empty() {
+            bail!(ErrorKind::BootstrapEntidsInUse(self.to_version(), in_use));
+        }
+        Ok(())
+    }
+
+    /// Apply this migration step.  The caller is responsible for wrapping the step in a SQLite
+    /// transaction.
+    fn apply(&self, conn: &rusqlite::Connection) -> Result<(i32, i32)> {
+        self.ensure_new_entids_unused(conn)?;
+
+        for statement in &self.statements {
+            conn.execute(statement, &[])
+                .chain_err(|| format!("Failed to execute migration statement: {}", statement))?;
+        }
+
+        // Stores written by older versions can have materialized views that disagree with their
+        // datoms, and transacting only updates the views of the entities it changes, so rebuild
+        // the views first.
+        rebuild_idents_and_schema(conn)?;
+
+        // Transacting bootstrap entities that are already present is a no-op, so steps can
+        // transact the complete set of bootstrap entities of their version rather than computing
+        // differences.
+        let partition_map = read_partition_map(conn)?;
+        let bootstrap_schema = bootstrap::bootstrap_schema_for_version(self.to_version());
+        transact(conn, partition_map, &bootstrap_schema, bootstrap::bootstrap_entities_for_version(self.to_version()))?;
+
+        set_user_version(conn, self.to_version())?;
+
+        Ok((self.from_version, self.to_version()))
+    }
+}
+
+lazy_static! {
+    /// Migration steps, ordered by `from_version`.  See `CURRENT_VERSION` for the version history.
+    static ref MIGRATIONS: Vec<Migration> = vec![
+        // We assigned idents 36 and 37 in :db.part/db, so we bump the part range.  Version 1 stores
+        // record the last entid allocated in :db.part/user and :db.part/tx rather than the next
+        // one, so we bump those ranges too.
+        Migration {
+            from_version: 1,
+            statements: vec![r#"UPDATE parts SET idx = idx + 2 WHERE part = ':db.part/db'"#.to_string(),
+                             r#"UPDATE parts SET idx = idx + 1 WHERE part IN (':db.part/user', ':db.part/tx')"#.to_string()],
+        },
+        // Version 2 stores written by Datomish also record the last entid allocated in
+        // :db.part/user and :db.part/tx, so we bump those ranges past any entid that's already in
+        // use.
+        Migration {
+            from_version: 2,
+            statements: vec![r#"CREATE INDEX idx_transactions_eavt ON transactions (e, a, value_type_tag, v)"#.to_string(),
+                             r#"UPDATE parts SET idx = idx + 1
+                                WHERE part IN (':db.part/user', ':db.part/tx')
+                                  AND EXISTS (SELECT 1 FROM transactions WHERE e = parts.idx)"#.to_string()],
+        },
+        // The excision attributes are new schema for existing idents.  Excision garbage collects
+        // fulltext values, which needs to find the transactions that reference them.
+        Migration {
+            from_version: 3,
+            statements: vec![r#"CREATE INDEX idx_transactions_fulltext ON transactions (v) WHERE value_type_tag = 10 AND typeof(v) = 'integer'"#.to_string()],
+        },
+        // :db.revert/tx is assigned a reserved entid, so the :db.part/db index doesn't change.
+        Migration {
+            from_version: 4,
+            statements: vec![],
+        },
+        // The transactor writes :db/txInstant as a microsecond instant whatever the schema, so
+        // earlier steps already did; only rewrite the millisecond longs that predate them.  Version
+        // 1 stores written by Datomish hold their millisecond timestamps, including user instants,
+        // as reals.
+        Migration {
+            from_version: 5,
+            statements: ["datoms", "transactions"].iter().map(|table| format!(
+                r#"UPDATE {table} SET v = CAST(v * 1000 AS INTEGER), value_type_tag = 4
+                   WHERE (a IN ({tx_instant}, {excise_before}) AND value_type_tag = 5) OR (value_type_tag = 4 AND typeof(v) = 'real')"#,
+                table = table,
+                tx_instant = entids::DB_TX_INSTANT,
+                excise_before = entids::DB_EXCISE_BEFORE)).collect(),
+        },
+        // :db.type/uuid and :db.type/uri are assigned reserved entids, so the :db.part/db index
+        // doesn't change.
+        Migration {
+            from_version: 6,
+            statements: vec![],
+        },
+        // :db/indexBytes is assigned a reserved entid, so the :db.part/db index doesn't change.
+        Migration {
+            from_version: 7,
+            statements: vec![],
+        },
+    ];
+}
+
+/// Migrate the SQL store from `current_version` to `CURRENT_VERSION`.
+///
+/// Each migration step is applied in its own SQLite transaction, so a failing step leaves the store
+/// at the version before that step.  Returns the `(from, to)` versions of each step applied, in
+/// order.
+pub fn update_from_version(conn: &mut rusqlite::Connection, current_version: i32) -> Result<Vec<(i32, i32)>> {
+    if current_version <= 0 || CURRENT_VERSION <= current_version {
         bail!(ErrorKind::BadSQLiteStoreVersion(current_version))
     }
 
-    let tx = conn.transaction()?;
-    // TODO: actually implement upgrade.
-    set_user_version(&tx, CURRENT_VERSION)?;
-    let user_version = get_user_version(&tx)?;
-    // TODO: use the drop semantics to do this automagically?
-    tx.commit()?;
+    let mut steps = vec![];
+    let mut version = current_version;
+    while version < CURRENT_VERSION {
+        let migration = MIGRATIONS.iter()
+            .find(|migration| migration.from_version == version)
+            .ok_or(ErrorKind::BadSQLiteStoreVersion(version))?;
+
+        let tx = conn.transaction()?;
+        let step = migration.apply(&tx)?;
+        // TODO: use the drop semantics to do this automagically?
+        tx.commit()?;
 
-    Ok(user_version)
+        version = step.1;
+        steps.push(step);
+    }
+
+    Ok(steps)
 }
 
+/// Create a new Mentat store, or re-open an existing Mentat store, returning the `DB` metadata
+/// required to query from and transact against the store.
 pub fn ensure_current_version(conn: &mut rusqlite::Connection) -> Result<DB> {
     let user_version = get_user_version(&conn)?;
     match user_version {
         0 => create_current_version(conn),
-        // TODO: support updating or re-opening an existing store.
-        v => bail!(ErrorKind::NotYetImplemented(format!("Opening databases with Mentat version: {}", v))),
+        CURRENT_VERSION => read_db(conn),
+        v if v < CURRENT_VERSION => {
+            update_from_version(conn, v)?;
+            read_db(conn)
+        },
+        v => bail!(ErrorKind::BadSQLiteStoreVersion(v)),
     }
 }
 
@@ -345,11 +545,24 @@ impl TypedSQLValue for TypedValue {
         match (value_type_tag, value) {
             (0, rusqlite::types::Value::Integer(x)) => Ok(TypedValue::Ref(x)),
             (1, rusqlite::types::Value::Integer(x)) => Ok(TypedValue::Boolean(0 != x)),
+            (2, rusqlite::types::Value::Blob(x)) => Ok(TypedValue::Bytes(x)),
+            (4, rusqlite::types::Value::Integer(x)) => {
+                match DateTime::<Utc>::from_micros(x) {
+                    Some(instant) => Ok(TypedValue::Instant(instant)),
+                    None => bail!(ErrorKind::BadSQLValuePair(rusqlite::types::Value::Integer(x), value_type_tag)),
+                }
+            },
             // SQLite distinguishes integral from decimal types, allowing long and double to
             // share a tag.
             (5, rusqlite::types::Value::Integer(x)) => Ok(TypedValue::Long(x)),
             (5, rusqlite::types::Value::Real(x)) => Ok(TypedValue::Double(x.into())),
             (10, rusqlite::types::Value::Text(x)) => Ok(TypedValue::String(x)),
+            (11, rusqlite::types::Value::Blob(x)) => {
+                Uuid::from_bytes(&x)
+                    .map(|u| TypedValue::Uuid(u))
+                    .map_err(|_| ErrorKind::BadSQLValuePair(rusqlite::types::Value::Blob(x.clone()), value_type_tag).into())
+            },
+            (12, rusqlite::types::Value::Text(x)) => Ok(TypedValue::Uri(x)),
             (13, rusqlite::types::Value::Text(x)) => {
                 to_namespaced_keyword(&x).map(|k| TypedValue::Keyword(k))
             },
@@ -369,6 +582,9 @@ impl TypedSQLValue for TypedValue {
             &Value::Boolean(x) => Some(TypedValue::Boolean(x)),
             &Value::Integer(x) => Some(TypedValue::Long(x)),
             &Value::Float(ref x) => Some(TypedValue::Double(x.clone())),
+            &Value::Instant(x) => Some(TypedValue::instant(x)),
+            &Value::Uuid(x) => Some(TypedValue::Uuid(x)),
+            &Value::Bytes(ref x) => Some(TypedValue::Bytes(x.clone())),
             &Value::Text(ref x) => Some(TypedValue::String(x.clone())),
             &Value::NamespacedKeyword(ref x) => Some(TypedValue::Keyword(x.clone())),
             _ => None
@@ -383,8 +599,13 @@ impl TypedSQLValue for TypedValue {
             // SQLite distinguishes integral from decimal types, allowing long and double to share a tag.
             &TypedValue::Long(x) => (rusqlite::types::Value::Integer(x).into(), 5),
             &TypedValue::Double(x) => (rusqlite::types::Value::Real(x.into_inner()).into(), 5),
+            &TypedValue::Instant(x) => (rusqlite::types::Value::Integer(x.to_micros()).into(), 4),
             &TypedValue::String(ref x) => (rusqlite::types::ValueRef::Text(x.as_str()).into(), 10),
             &TypedValue::Keyword(ref x) => (rusqlite::types::ValueRef::Text(&x.to_string()).into(), 13),
+            // UUIDs are stored compactly, as their 16 bytes.
+            &TypedValue::Uuid(ref x) => (rusqlite::types::ValueRef::Blob(&x.as_bytes()[..]).into(), 11),
+            &TypedValue::Uri(ref x) => (rusqlite::types::ValueRef::Text(x.as_str()).into(), 12),
+            &TypedValue::Bytes(ref x) => (rusqlite::types::ValueRef::Blob(x.as_slice()).into(), 2),
         }
     }
 
@@ -395,8 +616,12 @@ impl TypedSQLValue for TypedValue {
             &TypedValue::Boolean(x) => (Value::Boolean(x), ValueType::Boolean),
             &TypedValue::Long(x) => (Value::Integer(x), ValueType::Long),
             &TypedValue::Double(x) => (Value::Float(x), ValueType::Double),
+            &TypedValue::Instant(x) => (Value::Instant(x), ValueType::Instant),
             &TypedValue::String(ref x) => (Value::Text(x.clone()), ValueType::String),
             &TypedValue::Keyword(ref x) => (Value::NamespacedKeyword(x.clone()), ValueType::Keyword),
+            &TypedValue::Uuid(x) => (Value::Uuid(x), ValueType::Uuid),
+            &TypedValue::Uri(ref x) => (Value::Text(x.clone()), ValueType::Uri),
+            &TypedValue::Bytes(ref x) => (Value::Bytes(x.clone()), ValueType::Bytes),
         }
     }
 }
@@ -445,6 +670,134 @@ pub fn read_schema(conn: &rusqlite::Connection, ident_map: &IdentMap) -> Result<
     r.and_then(|triples| Schema::from_ident_map_and_triples(ident_map.clone(), triples))
 }
 
+/// Rebuild the `idents` and `schema` materialized views from the `datoms` table.
+///
+/// Every [e :db/ident v] datom becomes a row of `idents`.  Every schema assertion [e a v] about an
+/// entity with a `:db/valueType` becomes a row of `schema`.
+pub fn rebuild_idents_and_schema(conn: &rusqlite::Connection) -> Result<()> {
+    // `schema` references `idents`, so delete it first and insert it last.
+    conn.execute_batch("DELETE FROM schema; DELETE FROM idents;")
+        .chain_err(|| "Could not rebuild idents and schema")?;
+
+    insert_idents_and_schema(conn, None)
+        .chain_err(|| "Could not rebuild idents and schema")
+}
+
+/// Update the `idents` and `schema` materialized views for the given entities only.
+///
+/// This is the incremental version of `rebuild_idents_and_schema`, for transactions that change
+/// the idents or schema of a few entities.  Changing one of the metadata attributes themselves
+/// can change every row of `schema`, so that rebuilds the views completely.
+pub fn update_idents_and_schema(conn: &rusqlite::Connection, entities: &BTreeSet<Entid>) -> Result<()> {
+    if entities.iter().any(|&e| entids::might_update_metadata(e)) {
+        return rebuild_idents_and_schema(conn);
+    }
+
+    let s = format!(r#"
+      DELETE FROM schema WHERE ident IN (SELECT ident FROM idents WHERE entid IN ({entities}));
+      DELETE FROM idents WHERE entid IN ({entities});"#,
+      entities = entities.iter().join(", "));
+
+    conn.execute_batch(&s)
+        .chain_err(|| "Could not update idents and schema")?;
+
+    insert_idents_and_schema(conn, Some(entities))
+        .chain_err(|| "Could not update idents and schema")
+}
+
+/// Insert the `idents` and `schema` rows of the given entities, or of every entity if `entities` is
+/// `None`, from the `datoms` table.
+fn insert_idents_and_schema(conn: &rusqlite::Connection, entities: Option<&BTreeSet<Entid>>) -> Result<()> {
+    let restriction = match entities {
+        Some(entities) => format!("AND e IN ({})", entities.iter().join(", ")),
+        None => "".to_string(),
+    };
+
+    let s = format!(r#"
+      INSERT INTO idents (ident, entid)
+      SELECT v, e FROM datoms WHERE a = {db_ident} {restriction};
+
+      INSERT INTO schema (ident, attr, value, value_type_tag)
+      SELECT i.ident, j.ident, d.v, d.value_type_tag
+      FROM datoms AS d, idents AS i, idents AS j
+      WHERE d.e = i.entid AND
+            d.a = j.entid AND
+            d.a IN ({db_value_type}, {db_cardinality}, {db_unique}, {db_is_component}, {db_index}, {db_fulltext}, {db_no_history}, {db_index_bytes}, {db_doc}) AND
+            d.e IN (SELECT e FROM datoms WHERE a = {db_value_type} {restriction});"#,
+      restriction = restriction,
+      db_ident = entids::DB_IDENT,
+      db_value_type = entids::DB_VALUE_TYPE,
+      db_cardinality = entids::DB_CARDINALITY,
+      db_unique = entids::DB_UNIQUE,
+      db_is_component = entids::DB_IS_COMPONENT,
+      db_index = entids::DB_INDEX,
+      db_fulltext = entids::DB_FULLTEXT,
+      db_no_history = entids::DB_NO_HISTORY,
+      db_index_bytes = entids::DB_INDEX_BYTES,
+      db_doc = entids::DB_DOC);
+
+    conn.execute_batch(&s)?;
+    Ok(())
+}
+
+/// Check that the existing datoms of each attribute altered between `schema` and `next_schema` are
+/// valid under the altered attribute, and rewrite their flag columns to match.
+///
+/// Changing `:db/valueType` or `:db/fulltext` changes how values are stored, and is only allowed
+/// for attributes without datoms.
+pub fn alter_attributes(conn: &rusqlite::Connection, schema: &Schema, next_schema: &Schema) -> Result<()> {
+    for (&entid, next_attribute) in &next_schema.schema_map {
+        let attribute = match schema.attribute_for_entid(entid) {
+            Some(attribute) if attribute != next_attribute => attribute,
+            _ => continue,
+        };
+
+        let ident = next_schema.require_ident(entid)?.to_string();
+
+        if attribute.value_type != next_attribute.value_type || attribute.fulltext != next_attribute.fulltext {
+            let exists: bool = conn.query_row("SELECT EXISTS (SELECT 1 FROM datoms WHERE a = ?)", &[&entid], |row| row.get(0))?;
+            if exists {
+                bail!(ErrorKind::SchemaAlterationFailed(ident, "cannot change :db/valueType or :db/fulltext of an attribute with datoms".to_string()));
+            }
+        }
+
+        if !attribute.unique_value && next_attribute.unique_value {
+            let exists: bool = conn.query_row("SELECT EXISTS (SELECT 1 FROM datoms WHERE a = ? GROUP BY value_type_tag, v HAVING COUNT(*) > 1)", &[&entid], |row| row.get(0))?;
+            if exists {
+                bail!(ErrorKind::SchemaAlterationFailed(ident, "cannot make attribute :db/unique: some value is asserted for more than one entity".to_string()));
+            }
+        }
+
+        if attribute.multival && !next_attribute.multival {
+            let exists: bool = conn.query_row("SELECT EXISTS (SELECT 1 FROM datoms WHERE a = ? GROUP BY e HAVING COUNT(*) > 1)", &[&entid], |row| row.get(0))?;
+            if exists {
+                bail!(ErrorKind::SchemaAlterationFailed(ident, "cannot make attribute :db.cardinality/one: some entity has more than one value".to_string()));
+            }
+        }
+
+        let flags = next_attribute.flags();
+        if attribute.flags() != flags {
+            let s = r#"
+              UPDATE datoms
+              SET index_avet = ? & ? IS NOT 0,
+                  index_vaet = ? & ? IS NOT 0,
+                  index_fulltext = ? & ? IS NOT 0,
+                  unique_value = ? & ? IS NOT 0
+              WHERE a = ?"#;
+            let mut stmt = conn.prepare_cached(s)?;
+            stmt.execute(&[&flags, &(AttributeBitFlags::IndexAVET as u8),
+                           &flags, &(AttributeBitFlags::IndexVAET as u8),
+                           &flags, &(AttributeBitFlags::IndexFulltext as u8),
+                           &flags, &(AttributeBitFlags::UniqueValue as u8),
+                           &entid])
+                .map(|_c| ())
+                .chain_err(|| "Could not update datoms: failed to rewrite attribute flags")?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Read the materialized views from the given SQL store and return a Mentat `DB` for querying and
 /// applying transactions.
 pub fn read_db(conn: &rusqlite::Connection) -> Result<DB> {
@@ -489,6 +842,12 @@ pub trait MentatStoring {
     // TODO: this is not a reasonable abstraction, but I don't want to really consider non-SQL storage just yet.
     fn insert_non_fts_searches<'a>(&self, entities: &'a [ReducedEntity], search_type: SearchType) -> Result<()>;
 
+    /// Insert search rows for :db/fulltext assertions and retractions.
+    ///
+    /// Each string value is interned into the fulltext index, and the search rows refer to the
+    /// interned value by its rowid.
+    fn insert_fts_searches<'a>(&self, entities: &'a [ReducedEntity], search_type: SearchType) -> Result<()>;
+
     /// Finalize the underlying storage layer after a Mentat transaction.
     ///
     /// Use this to finalize temporary tables, complete indices, revert pragmas, etc, after the
@@ -501,7 +860,8 @@ pub trait MentatStoring {
 /// See https://github.com/mozilla/mentat/wiki/Transacting:-entity-to-SQL-translation.
 fn search(conn: &rusqlite::Connection) -> Result<()> {
     // First is fast, only one table walk: lookup by exact eav.
-    // Second is slower, but still only one table walk: lookup old value by ea.
+    // Second is slower, but still only one table walk: lookup old value by ea.  Retractions only
+    // match the exact value being retracted.
     let s = r#"
       INSERT INTO temp.search_results
       SELECT t.e0, t.a0, t.v0, t.value_type_tag0, t.added0, t.flags0, ':db.cardinality/many', d.rowid, d.v
@@ -518,7 +878,8 @@ fn search(conn: &rusqlite::Connection) -> Result<()> {
       FROM temp.inexact_searches AS t
       LEFT JOIN datoms AS d
       ON t.e0 = d.e AND
-         t.a0 = d.a"#;
+         t.a0 = d.a AND
+         (t.added0 IS 1 OR (t.value_type_tag0 = d.value_type_tag AND t.v0 = d.v))"#;
 
     let mut stmt = conn.prepare_cached(s)?;
     stmt.execute(&[])
@@ -543,19 +904,44 @@ fn insert_transaction(conn: &rusqlite::Connection, tx: Entid) -> Result<()> {
         .map(|_c| ())
         .chain_err(|| "Could not insert transaction: failed to add datoms not already present")?;
 
-    let s = r#"
+    let s = format!(r#"
       INSERT INTO transactions (e, a, v, tx, added, value_type_tag)
       SELECT e0, a0, v, ?, 0, value_type_tag0
       FROM temp.search_results
       WHERE rid IS NOT NULL AND
+            flags0 & {no_history} IS 0 AND
             ((added0 IS 0) OR
-             (added0 IS 1 AND search_type IS ':db.cardinality/one' AND v0 IS NOT v))"#;
+             (added0 IS 1 AND search_type IS ':db.cardinality/one' AND v0 IS NOT v))"#,
+      no_history = AttributeBitFlags::NoHistory as u8);
 
-    let mut stmt = conn.prepare_cached(s)?;
+    let mut stmt = conn.prepare_cached(&s)?;
     stmt.execute(&[&tx])
         .map(|_c| ())
         .chain_err(|| "Could not insert transaction: failed to retract datoms already present")?;
 
+    // Attributes with `:db/noHistory true` don't accumulate retractions: rather than recording the
+    // retraction, forget that the retracted datom was ever asserted.  We start from the (few)
+    // retracted datoms and find their transactions through `idx_transactions_eavt`, rather than
+    // visiting every transaction.
+    let s = format!(r#"
+      DELETE FROM transactions
+      WHERE rowid IN (SELECT t.rowid
+                      FROM temp.search_results AS s, transactions AS t
+                      WHERE s.rid IS NOT NULL AND
+                            s.flags0 & {no_history} IS NOT 0 AND
+                            ((s.added0 IS 0) OR
+                             (s.added0 IS 1 AND s.search_type IS ':db.cardinality/one' AND s.v0 IS NOT s.v)) AND
+                            t.e = s.e0 AND
+                            t.a = s.a0 AND
+                            t.value_type_tag = s.value_type_tag0 AND
+                            t.v = s.v)"#,
+      no_history = AttributeBitFlags::NoHistory as u8);
+
+    let mut stmt = conn.prepare_cached(&s)?;
+    stmt.execute(&[])
+        .map(|_c| ())
+        .chain_err(|| "Could not insert transaction: failed to forget datoms without history")?;
+
     Ok(())
 }
 
@@ -791,6 +1177,86 @@ impl MentatStoring for rusqlite::Connection {
         results.map(|_| ())
     }
 
+    fn insert_fts_searches<'a>(&self, entities: &'a [ReducedEntity<'a>], search_type: SearchType) -> Result<()> {
+        let bindings_per_statement = 6;
+
+        let max_vars = self.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER) as usize;
+        let chunks: itertools::IntoChunks<_> = entities.into_iter().chunks(max_vars / bindings_per_statement);
+
+        // Each distinct string is inserted into `fulltext_values` with a temporary search ID, which
+        // the search rows use to find the rowid of the (possibly pre-existing) fulltext value.
+        // Inserting the same string twice would overwrite its search ID, so we track the strings
+        // we've already seen.
+        let mut outer_searchid = 2000;
+        let mut seen: HashMap<&'a str, i64> = HashMap::with_capacity(entities.len());
+
+        let results: Result<Vec<()>> = chunks.into_iter().map(|chunk| -> Result<()> {
+            // (text, searchid) for strings not yet inserted.
+            let mut strings: Vec<(&'a str, i64)> = vec![];
+
+            // (e0, a0, searchid, value_type_tag0, added0, flags0)
+            let mut block: Vec<(i64, i64, i64, i32, bool, u8)> = vec![];
+
+            for &(e, a, ref attribute, ref typed_value, added) in chunk {
+                let text: &'a str = match typed_value {
+                    &TypedValue::String(ref text) => text.as_str(),
+                    // Fulltext attributes are always :db.type/string, and values have been
+                    // type-checked, so this shouldn't happen.
+                    _ => bail!(ErrorKind::BadFulltextValue(typed_value.clone())),
+                };
+
+                let searchid = *seen.entry(text).or_insert_with(|| {
+                    outer_searchid += 1;
+                    strings.push((text, outer_searchid));
+                    outer_searchid
+                });
+
+                block.push((e, a, searchid, ValueType::String.value_type_tag(), added, attribute.flags()));
+            }
+
+            if !strings.is_empty() {
+                // `fts_params` reference values in `strings`.
+                let fts_params: Vec<&ToSql> = strings.iter().flat_map(|&(ref text, ref searchid)| {
+                    once(text as &ToSql)
+                        .chain(once(searchid as &ToSql))
+                }).collect();
+
+                let fts_s: String = format!("INSERT INTO fulltext_values_view (text, searchid) VALUES {}", repeat_values(2, strings.len()));
+                let mut stmt = self.prepare_cached(fts_s.as_str())?;
+                stmt.execute(&fts_params)
+                    .chain_err(|| "Could not insert fulltext values!")?;
+            }
+
+            // `params` reference computed values in `block`.
+            let params: Vec<&ToSql> = block.iter().flat_map(|&(ref e, ref a, ref searchid, ref value_type_tag, added, ref flags)| {
+                once(e as &ToSql)
+                    .chain(once(a as &ToSql)
+                           .chain(once(searchid as &ToSql)
+                                  .chain(once(value_type_tag as &ToSql)
+                                         .chain(once(to_bool_ref(added) as &ToSql)
+                                                .chain(once(flags as &ToSql))))))
+            }).collect();
+
+            assert!(bindings_per_statement * block.len() < max_vars, "Too many values: {} * {} >= {}", bindings_per_statement, block.len(), max_vars);
+            let values: String = repeat("(?, ?, (SELECT rowid FROM fulltext_values WHERE searchid = ?), ?, ?, ?)").take(block.len()).join(", ");
+            let s: String = if search_type == SearchType::Exact {
+                format!("INSERT INTO temp.exact_searches (e0, a0, v0, value_type_tag0, added0, flags0) VALUES {}", values)
+            } else {
+                format!("INSERT INTO temp.inexact_searches (e0, a0, v0, value_type_tag0, added0, flags0) VALUES {}", values)
+            };
+
+            let mut stmt = self.prepare_cached(s.as_str())?;
+            stmt.execute(&params)
+                .map(|_c| ())
+                .chain_err(|| "Could not insert fts statements into temporary search table!")
+        }).collect::<Result<Vec<()>>>();
+
+        // Search IDs are only meaningful for the duration of this insertion.
+        self.execute("UPDATE fulltext_values SET searchid = NULL WHERE searchid IS NOT NULL", &[])?;
+
+        results.map(|_| ())
+    }
+
     fn commit_transaction(&self, tx_id: Entid) -> Result<()> {
         search(&self)?;
         insert_transaction(&self, tx_id)?;
@@ -799,6 +1265,379 @@ impl MentatStoring for rusqlite::Connection {
     }
 }
 
+/// Find an assertion in the pending search rows that would violate a :db/unique attribute, either
+/// because another entity already has the asserted [a v] in the store and keeps it after this
+/// transaction, or because another entity asserts the same [a v] in this transaction.
+///
+/// Returns the first violation found as (a, v, existing entid), if there is one.  This must be
+/// invoked after all search rows are inserted and before `commit_transaction`.
+pub fn find_unique_value_violation(conn: &rusqlite::Connection) -> Result<Option<(Entid, TypedValue, Entid)>> {
+    let s = format!(r#"
+      WITH searches AS (SELECT e0, a0, v0, value_type_tag0, added0, flags0, 0 AS inexact FROM temp.exact_searches
+                        UNION ALL
+                        SELECT e0, a0, v0, value_type_tag0, added0, flags0, 1 AS inexact FROM temp.inexact_searches),
+           added AS (SELECT * FROM searches WHERE added0 IS 1 AND flags0 & {unique} IS NOT 0)
+      SELECT t.a0, coalesce(f.text, t.v0), t.value_type_tag0, d.e
+      FROM added AS t
+      JOIN datoms AS d
+      ON d.a = t.a0 AND d.value_type_tag = t.value_type_tag0 AND d.v = t.v0 AND d.e IS NOT t.e0
+      LEFT JOIN fulltext_values AS f
+      ON t.flags0 & {fulltext} IS NOT 0 AND f.rowid = t.v0
+      WHERE NOT EXISTS (SELECT 1 FROM searches AS r
+                        WHERE r.added0 IS 0 AND r.e0 = d.e AND r.a0 = d.a AND r.value_type_tag0 = d.value_type_tag AND r.v0 = d.v)
+        AND NOT EXISTS (SELECT 1 FROM searches AS r
+                        WHERE r.inexact IS 1 AND r.added0 IS 1 AND r.e0 = d.e AND r.a0 = d.a AND
+                              NOT (r.value_type_tag0 = d.value_type_tag AND r.v0 = d.v))
+
+      UNION ALL
+
+      SELECT t.a0, coalesce(f.text, t.v0), t.value_type_tag0, o.e0
+      FROM added AS t
+      JOIN added AS o
+      ON o.a0 = t.a0 AND o.value_type_tag0 = t.value_type_tag0 AND o.v0 = t.v0 AND o.e0 < t.e0
+      LEFT JOIN fulltext_values AS f
+      ON t.flags0 & {fulltext} IS NOT 0 AND f.rowid = t.v0
+
+      LIMIT 1"#,
+      unique = AttributeBitFlags::UniqueValue as u8,
+      fulltext = AttributeBitFlags::IndexFulltext as u8);
+
+    let mut stmt: rusqlite::Statement = conn.prepare(&s)?;
+
+    let r: Result<Vec<(Entid, TypedValue, Entid)>> = stmt.query_and_then(&[], |row| {
+        let a: Entid = row.get_checked(0)?;
+        let v: rusqlite::types::Value = row.get_checked(1)?;
+        let value_type_tag: i32 = row.get_checked(2)?;
+        let e: Entid = row.get_checked(3)?;
+        Ok((a, TypedValue::from_sql_value_pair(v, value_type_tag)?, e))
+    })?.collect();
+
+    Ok(r?.into_iter().next())
+}
+
+/// Read the current values [v] of attribute `a` of entity `e`.  Fulltext values are read from the
+/// fulltext index.
+pub fn read_values(conn: &rusqlite::Connection, e: Entid, a: Entid) -> Result<Vec<TypedValue>> {
+    let mut stmt = conn.prepare_cached("SELECT v, value_type_tag FROM all_datoms WHERE e = ? AND a = ?")?;
+
+    let r: Result<Vec<TypedValue>> = stmt.query_and_then(&[&e, &a], |row| {
+        let v: rusqlite::types::Value = row.get_checked(0)?;
+        let value_type_tag: i32 = row.get_checked(1)?;
+        TypedValue::from_sql_value_pair(v, value_type_tag)
+    })?.collect();
+    r
+}
+
+/// Read the datoms [e a v] to retract in order to retract the entity `e`: every datom with entity
+/// `e` or with ref value `e`, and, recursively, the datoms of the entities that `e` refers to
+/// through the given `component_attributes`.
+pub fn read_retract_entity_datoms(conn: &rusqlite::Connection, e: Entid, component_attributes: &[Entid]) -> Result<Vec<(Entid, Entid, TypedValue)>> {
+    let s = format!(r#"
+      WITH RECURSIVE es(e) AS (
+        SELECT ?
+        UNION
+        SELECT d.v
+        FROM datoms AS d, es
+        WHERE d.e = es.e AND d.a IN ({}) AND d.value_type_tag = 0)
+      SELECT e, a, v, value_type_tag FROM all_datoms WHERE e IN es
+      UNION
+      SELECT e, a, v, value_type_tag FROM all_datoms WHERE v IN es AND value_type_tag = 0
+      ORDER BY e, a, v"#,
+      component_attributes.iter().join(", "));
+
+    let mut stmt: rusqlite::Statement = conn.prepare(&s)?;
+
+    let r: Result<Vec<(Entid, Entid, TypedValue)>> = stmt.query_and_then(&[&e], |row| {
+        let e: Entid = row.get_checked(0)?;
+        let a: Entid = row.get_checked(1)?;
+        let v: rusqlite::types::Value = row.get_checked(2)?;
+        let value_type_tag: i32 = row.get_checked(3)?;
+        Ok((e, a, TypedValue::from_sql_value_pair(v, value_type_tag)?))
+    })?.collect();
+    r
+}
+
+/// Permanently remove datoms about entity `e` from both `datoms` and `transactions`, and then any
+/// fulltext values no longer referenced.
+///
+/// Only datoms with an attribute in `attributes` are removed, or all of `e`'s datoms if
+/// `attributes` is empty.  Only datoms from transactions before `before_tx` are removed, and, if
+/// given, only those from transactions with a :db/txInstant before `before_instant`.
+pub fn excise(conn: &rusqlite::Connection, e: Entid, attributes: &[Entid], before_tx: Entid, before_instant: Option<DateTime<Utc>>) -> Result<()> {
+    let mut clauses = vec![format!("e = {}", e), format!("tx < {}", before_tx)];
+    if !attributes.is_empty() {
+        clauses.push(format!("a IN ({})", attributes.iter().join(", ")));
+    }
+    if let Some(before_instant) = before_instant {
+        clauses.push(format!("tx IN (SELECT e FROM datoms WHERE a = {} AND v < {})", entids::DB_TX_INSTANT, before_instant.to_micros()));
+    }
+    let clauses = clauses.join(" AND ");
+
+    // Every datom is also in the transaction log, so the fulltext values of the excised log
+    // entries are the only fulltext values that excision can leave unreferenced.
+    let s = format!(r#"
+      SELECT DISTINCT v FROM transactions
+      WHERE {clauses} AND value_type_tag = 10 AND typeof(v) = 'integer'"#,
+      clauses = clauses);
+    let mut stmt = conn.prepare(&s)?;
+    let rowids: Result<Vec<i64>> = stmt.query_and_then(&[], |row| Ok(row.get_checked(0)?))?.collect();
+    let rowids = rowids?;
+
+    let s = format!(r#"
+      DELETE FROM transactions WHERE {clauses};
+      DELETE FROM datoms WHERE {clauses};"#,
+      clauses = clauses);
+
+    conn.execute_batch(&s)
+        .chain_err(|| format!("Could not excise entity {}", e))?;
+
+    garbage_collect_fulltext_values(conn, &rowids)
+}
+
+/// Read the :db/txInstant of the most recent transaction, if there is one.
+///
+/// Stores not yet migrated to version 6 hold millisecond :db/txInstant values, as longs or reals;
+/// those always precede microsecond instants, and so are ignored.
+pub fn read_last_tx_instant(conn: &rusqlite::Connection) -> Result<Option<DateTime<Utc>>> {
+    let mut stmt = conn.prepare_cached(r#"
+      SELECT v FROM transactions
+      WHERE a = ? AND added = 1 AND value_type_tag = 4 AND typeof(v) = 'integer'
+      ORDER BY tx DESC LIMIT 1"#)?;
+
+    let r: Result<Vec<DateTime<Utc>>> = stmt.query_and_then(&[&entids::DB_TX_INSTANT], |row| {
+        let micros: i64 = row.get_checked(0)?;
+        DateTime::<Utc>::from_micros(micros)
+            .ok_or_else(|| ErrorKind::BadSQLValuePair(rusqlite::types::Value::Integer(micros), 4).into())
+    })?.collect();
+
+    Ok(r?.into_iter().next())
+}
+
+/// Read the datoms asserted and retracted by the transaction with the given `tx_id`, in [e a v added]
+/// order.  Fulltext values are read from the fulltext index.
+pub fn read_tx_data(conn: &rusqlite::Connection, tx_id: Entid) -> Result<Vec<Datom>> {
+    let mut stmt = conn.prepare_cached(r#"
+      SELECT t.e, t.a, coalesce(f.text, t.v), t.value_type_tag, t.added
+      FROM transactions AS t
+      LEFT JOIN fulltext_values AS f
+      ON t.value_type_tag = 10 AND typeof(t.v) = 'integer' AND f.rowid = t.v
+      WHERE t.tx = ?
+      ORDER BY t.e ASC, t.a ASC, t.v ASC, t.added ASC"#)?;
+
+    let r: Result<Vec<Datom>> = stmt.query_and_then(&[&tx_id], |row| {
+        let e: Entid = row.get_checked(0)?;
+        let a: Entid = row.get_checked(1)?;
+        let v: rusqlite::types::Value = row.get_checked(2)?;
+        let value_type_tag: i32 = row.get_checked(3)?;
+        let added: bool = row.get_checked(4)?;
+
+        Ok(Datom {
+            e: e,
+            a: a,
+            v: TypedValue::from_sql_value_pair(v, value_type_tag)?,
+            tx: tx_id,
+            added: added,
+        })
+    })?.collect();
+    r
+}
+
+/// Read the transactions with IDs in the half-open range `[start, end)` from the transaction log,
+/// in increasing transaction ID order.  If `end` is `None`, read every transaction from `start` on.
+///
+/// Transactions are read lazily, one at a time, as the returned iterator is advanced.
+pub fn tx_range<S, T>(conn: &rusqlite::Connection, schema: S, start: Entid, end: T) -> TxRange<S>
+    where S: Borrow<Schema>,
+          T: Into<Option<Entid>> {
+    TxRange {
+        conn: conn,
+        schema: schema,
+        next: start,
+        end: end.into().unwrap_or(::std::i64::MAX),
+    }
+}
+
+/// An iterator over a range of the transaction log.  See `tx_range`.
+pub struct TxRange<'c, S> where S: Borrow<Schema> {
+    conn: &'c rusqlite::Connection,
+    schema: S,
+    next: Entid,
+    end: Entid,
+}
+
+impl<'c, S> TxRange<'c, S> where S: Borrow<Schema> {
+    fn read_next(&mut self) -> Result<Option<TxLogEntry>> {
+        let tx_id: Option<Entid> = self.conn.query_row("SELECT min(tx) FROM transactions WHERE tx >= ? AND tx < ?", &[&self.next, &self.end], |row| row.get_checked(0))??;
+
+        let tx_id = match tx_id {
+            Some(tx_id) => tx_id,
+            None => return Ok(None),
+        };
+        self.next = tx_id + 1;
+
+        let schema = self.schema.borrow();
+        let mut tx_instant = None;
+        let mut tx_data = vec![];
+        for datom in read_tx_data(self.conn, tx_id)? {
+            if datom.e == tx_id && datom.a == entids::DB_TX_INSTANT && datom.added {
+                if let TypedValue::Instant(instant) = datom.v {
+                    tx_instant = Some(instant);
+                }
+            }
+            let a = schema.require_ident(datom.a)?.clone();
+            tx_data.push((datom.e, a, datom.v, datom.added));
+        }
+
+        let tx_instant = tx_instant.ok_or_else(|| ErrorKind::BadTxInstant(format!("transaction {} has no :db/txInstant", tx_id)))?;
+
+        Ok(Some(TxLogEntry {
+            tx_id: tx_id,
+            tx_instant: tx_instant,
+            tx_data: tx_data,
+        }))
+    }
+}
+
+impl<'c, S> Iterator for TxRange<'c, S> where S: Borrow<Schema> {
+    type Item = Result<TxLogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+
+        match self.read_next() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.next = self.end;
+                None
+            },
+            Err(e) => {
+                // Don't keep yielding the same error.
+                self.next = self.end;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+/// Produce the entities that revert the transaction with the given `tx_id`: retract each datom it
+/// asserted, re-assert each datom it retracted, and point the reverting transaction back at `tx_id`
+/// with :db.revert/tx.  The transaction's own metadata, like its :db/txInstant, is not reverted.
+///
+/// Unless `force` is true, fail if the transaction changed any :db/noHistory attribute, since the
+/// log doesn't remember the values that the transaction replaced, or if a later transaction changed
+/// any [e a] pair that the transaction changed, since reverting would clobber the later change.
+pub fn revert_entities(conn: &rusqlite::Connection, schema: &Schema, tx_id: Entid, force: bool) -> Result<Vec<Entity>> {
+    let datoms = read_tx_data(conn, tx_id)?;
+    if datoms.is_empty() {
+        bail!(ErrorKind::UnrecognizedEntid(tx_id));
+    }
+
+    if !force {
+        let mut no_history: BTreeSet<String> = BTreeSet::new();
+        for datom in datoms.iter().filter(|datom| datom.e != tx_id) {
+            if schema.require_attribute_for_entid(datom.a)?.no_history {
+                no_history.insert(schema.require_ident(datom.a)?.to_string());
+            }
+        }
+
+        if !no_history.is_empty() {
+            bail!(ErrorKind::RevertNoHistory(tx_id, no_history.into_iter().collect()));
+        }
+
+        let mut stmt = conn.prepare_cached(r#"
+          SELECT DISTINCT later.e, later.a
+          FROM transactions AS t
+          JOIN transactions AS later
+          ON later.e = t.e AND later.a = t.a AND later.tx > t.tx
+          WHERE t.tx = ? AND t.e != t.tx
+          ORDER BY later.e ASC, later.a ASC"#)?;
+
+        let conflicts: Result<Vec<(Entid, String)>> = stmt.query_and_then(&[&tx_id], |row| {
+            let e: Entid = row.get_checked(0)?;
+            let a: Entid = row.get_checked(1)?;
+            Ok((e, schema.require_ident(a)?.to_string()))
+        })?.collect();
+        let conflicts = conflicts?;
+
+        if !conflicts.is_empty() {
+            bail!(ErrorKind::RevertConflict(tx_id, conflicts));
+        }
+    }
+
+    // Re-asserting a :db.cardinality/one value retracts the current value, so we don't also
+    // retract it explicitly.
+    let mut reasserted: HashSet<(Entid, Entid)> = HashSet::new();
+    for datom in datoms.iter().filter(|datom| datom.e != tx_id && !datom.added) {
+        if !schema.require_attribute_for_entid(datom.a)?.multival {
+            reasserted.insert((datom.e, datom.a));
+        }
+    }
+
+    let mut entities: Vec<Entity> = datoms.into_iter()
+        .filter(|datom| datom.e != tx_id)
+        .filter(|datom| !(datom.added && reasserted.contains(&(datom.e, datom.a))))
+        .map(|datom| {
+            Entity::AddOrRetract {
+                op: if datom.added { OpType::Retract } else { OpType::Add },
+                e: entmod::EntidOrLookupRefOrTempId::Entid(entmod::Entid::Entid(datom.e)),
+                a: entmod::Entid::Entid(datom.a),
+                v: datom.v.to_edn_value_pair().0,
+            }
+        })
+        .collect();
+
+    entities.push(Entity::AddOrRetract {
+        op: OpType::Add,
+        e: entmod::EntidOrLookupRefOrTempId::Entid(entmod::Entid::Ident(symbols::NamespacedKeyword::new("db", "tx"))),
+        a: entmod::Entid::Entid(entids::DB_REVERT_TX),
+        v: Value::Integer(tx_id),
+    });
+
+    Ok(entities)
+}
+
+/// Read the rowids of the fulltext values retracted in the current transaction that didn't match
+/// any datom.
+///
+/// Searching for a retracted value inserts it into `fulltext_values`, so these are the only
+/// fulltext values that a transaction can leave unreferenced.
+pub fn unmatched_fulltext_retractions(conn: &rusqlite::Connection) -> Result<Vec<i64>> {
+    let s = format!(r#"
+      SELECT DISTINCT v0
+      FROM temp.search_results
+      WHERE added0 IS 0 AND rid IS NULL AND flags0 & {} IS NOT 0"#,
+      AttributeBitFlags::IndexFulltext as u8);
+
+    let mut stmt = conn.prepare_cached(&s)?;
+    let r: Result<Vec<i64>> = stmt.query_and_then(&[], |row| Ok(row.get_checked(0)?))?.collect();
+    r
+}
+
+/// Delete those of the fulltext values with the given `rowids` that are no longer referenced by
+/// any datom or transaction.
+///
+/// Fulltext datoms and transactions store the rowid of their string value in `fulltext_values`;
+/// non-fulltext string values are stored as text, and so are easy to distinguish.
+pub fn garbage_collect_fulltext_values(conn: &rusqlite::Connection, rowids: &[i64]) -> Result<()> {
+    // Bind each candidate so that SQLite looks up its references by index; correlating against
+    // the virtual table's rowid makes it scan the log.
+    let mut stmt = conn.prepare_cached(r#"
+      DELETE FROM fulltext_values
+      WHERE rowid = ?1
+        AND NOT EXISTS (SELECT 1 FROM datoms
+                        WHERE value_type_tag = 10 AND v = ?1 AND index_fulltext IS NOT 0)
+        AND NOT EXISTS (SELECT 1 FROM transactions
+                        WHERE value_type_tag = 10 AND typeof(v) = 'integer' AND v = ?1)"#)?;
+
+    for rowid in rowids {
+        stmt.execute(&[rowid])
+            .chain_err(|| "Could not garbage collect fulltext values!")?;
+    }
+    Ok(())
+}
+
 /// Update the current partition map materialized view.
 // TODO: only update changed partitions.
 pub fn update_partition_map(conn: &rusqlite::Connection, partition_map: &PartitionMap) -> Result<()> {
@@ -827,6 +1666,26 @@ pub fn update_partition_map(conn: &rusqlite::Connection, partition_map: &Partiti
         .chain_err(|| "Could not update partition map")
 }
 
+/// The number of entids set aside for each installed partition.
+///
+/// Installed partitions start at successive multiples of this size above the bootstrap partitions,
+/// so that the entids of each partition are contiguous.
+pub const INSTALLED_PARTITION_SIZE: i64 = 1 << 32;
+
+/// Install a new, empty partition named `part`, adding it to the given `partition_map` and
+/// persisting it to the `parts` materialized view.
+pub fn install_partition(conn: &rusqlite::Connection, partition_map: &mut PartitionMap, part: String) -> Result<()> {
+    let max_start = partition_map.values().map(|partition| partition.start).max().unwrap_or(0);
+    let start = (max_start / INSTALLED_PARTITION_SIZE + 1) * INSTALLED_PARTITION_SIZE;
+
+    let mut stmt = conn.prepare_cached("INSERT INTO parts VALUES (?, ?, ?)")?;
+    stmt.execute(&[&part, &start, &start])
+        .chain_err(|| format!("Could not install partition {}", part))?;
+
+    partition_map.insert(part, Partition::new(start, start));
+    Ok(())
+}
+
 pub trait PartitionMapping {
     fn allocate_entid<S: ?Sized + Ord + Display>(&mut self, partition: &S) -> i64 where String: Borrow<S>;
     fn allocate_entids<S: ?Sized + Ord + Display>(&mut self, partition: &S, n: usize) -> Range<i64> where String: Borrow<S>;
@@ -855,19 +1714,21 @@ impl PartitionMapping for PartitionMap {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::BTreeMap;
+    use chrono::Duration;
     use bootstrap;
     use debug;
     use edn;
     use edn::symbols;
+    use errors::Error;
     use mentat_tx_parser;
     use rusqlite;
     use tx::transact;
 
     #[test]
     fn test_open_current_version() {
-        // TODO: figure out how to reference the fixtures directory for real.  For now, assume we're
-        // executing `cargo test` in `db/`.
-        let conn = rusqlite::Connection::open("../fixtures/v2empty.db").unwrap();
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        create_current_version(&mut conn).unwrap();
 
         let ident_map = read_ident_map(&conn).unwrap();
         assert_eq!(ident_map, bootstrap::bootstrap_ident_map());
@@ -879,12 +1740,219 @@ mod tests {
 
         // Does not include :db/txInstant.
         let datoms = debug::datoms_after(&conn, &db.schema, 0).unwrap();
-        assert_eq!(datoms.0.len(), 88);
+        assert_eq!(datoms.0.len(), 110);
 
         // Includes :db/txInstant.
         let transactions = debug::transactions_after(&conn, &db.schema, 0).unwrap();
         assert_eq!(transactions.0.len(), 1);
-        assert_eq!(transactions.0[0].0.len(), 89);
+        assert_eq!(transactions.0[0].0.len(), 111);
+    }
+
+    #[test]
+    fn test_open_v2empty() {
+        let path = copy_fixture("v2empty.db", "open_v2empty");
+        let mut conn = new_connection(&path).expect("Couldn't open db");
+        assert_eq!(get_user_version(&conn).unwrap(), 2);
+
+        let db = ensure_current_version(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), CURRENT_VERSION);
+        assert_eq!(db.schema, bootstrap::bootstrap_schema());
+
+        // The :db.part/tx index is bumped past the bootstrap transaction, and each migration step
+        // is then a transaction of its own.
+        let mut expected_partition_map = bootstrap::bootstrap_partition_map();
+        expected_partition_map.get_mut(":db.part/tx").unwrap().index += 8;
+        assert_eq!(db.partition_map, expected_partition_map);
+
+        // The first step only transacts its :db/txInstant, the second installs the four :db/excise
+        // attributes, the third installs :db.revert/tx, the fourth retypes :db/txInstant and
+        // :db.excise/before, the fifth installs :db.type/uuid and :db.type/uri, and the sixth
+        // installs :db/indexBytes.
+        let transactions = debug::transactions_after(&conn, &db.schema, 0).unwrap();
+        assert_eq!(transactions.0.len(), 7);
+        assert_eq!(transactions.0[1].0.len(), 1);
+        assert_eq!(transactions.0[2].0.len(), 13);
+        assert_eq!(transactions.0[3].0.len(), 5);
+        assert_eq!(transactions.0[4].0.len(), 5);
+        assert_eq!(transactions.0[5].0.len(), 3);
+        assert_eq!(transactions.0[6].0.len(), 5);
+
+        // Every :db/txInstant, including the one written before migrating, is now an instant.
+        let longs: i64 = conn.query_row("SELECT count(*) FROM transactions WHERE a = ? AND value_type_tag != 4",
+                                        &[&entids::DB_TX_INSTANT], |row| row.get(0)).unwrap();
+        assert_eq!(longs, 0);
+
+        // Re-opening doesn't migrate again.
+        let reopened = ensure_current_version(&mut conn).unwrap();
+        assert_eq!(reopened, db);
+    }
+
+    #[test]
+    fn test_ensure_current_version_reopens_new_store() {
+        let path = debug::TempPath::new("reopen_new_store");
+
+        let created = {
+            let mut conn = new_connection(&path).expect("Couldn't open db");
+            ensure_current_version(&mut conn).unwrap()
+        };
+
+        let mut conn = new_connection(&path).expect("Couldn't re-open db");
+        assert_eq!(get_user_version(&conn).unwrap(), CURRENT_VERSION);
+        let reopened = ensure_current_version(&mut conn).unwrap();
+
+        assert_eq!(created, reopened);
+        assert_eq!(reopened.schema, bootstrap::bootstrap_schema());
+        assert_eq!(read_ident_map(&conn).unwrap(), bootstrap::bootstrap_ident_map());
+    }
+
+    /// Copy the given checked-in fixture to a temporary file that the test can modify.
+    fn copy_fixture(fixture: &str, label: &str) -> debug::TempPath {
+        let path = debug::TempPath::new(label);
+        ::std::fs::copy(format!("../fixtures/{}", fixture), &path).expect("Couldn't copy fixture");
+        path
+    }
+
+    #[test]
+    fn test_update_from_version_1() {
+        let path = copy_fixture("v1empty.db", "update_from_version_1");
+        let mut conn = new_connection(&path).expect("Couldn't open db");
+        assert_eq!(get_user_version(&conn).unwrap(), 1);
+
+        assert_eq!(update_from_version(&mut conn, 1).unwrap(), vec![(1, 2), (2, 3), (3, 4), (4, 5), (5, 6), (6, 7), (7, 8)]);
+        assert_eq!(get_user_version(&conn).unwrap(), CURRENT_VERSION);
+
+        // Already current; nothing to do.
+        assert!(update_from_version(&mut conn, CURRENT_VERSION).is_err());
+    }
+
+    #[test]
+    fn test_open_v1empty() {
+        let path = copy_fixture("v1empty.db", "open_v1empty");
+        let mut conn = new_connection(&path).expect("Couldn't open db");
+
+        let db = ensure_current_version(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), CURRENT_VERSION);
+
+        assert_eq!(read_ident_map(&conn).unwrap(), bootstrap::bootstrap_ident_map());
+        assert_eq!(db.schema, bootstrap::bootstrap_schema());
+
+        // The :db.part/db index is bumped past the new idents, and the :db.part/user and
+        // :db.part/tx indices are bumped past the last allocated entids.  Each migration step is
+        // then a transaction of its own.
+        let mut expected_partition_map = bootstrap::bootstrap_partition_map();
+        expected_partition_map.get_mut(":db.part/user").unwrap().index += 1;
+        expected_partition_map.get_mut(":db.part/tx").unwrap().index += 9;
+        assert_eq!(db.partition_map, expected_partition_map);
+
+        // The version 1 bootstrap transaction is untouched, the first migration step installs
+        // :db.schema/version and :db.schema/attribute, the second only transacts its :db/txInstant,
+        // the third installs the :db/excise attributes, the fourth installs :db.revert/tx, the
+        // fifth retypes :db/txInstant and :db.excise/before, the sixth installs :db.type/uuid and
+        // :db.type/uri, and the seventh installs :db/indexBytes.
+        let transactions = debug::transactions_after(&conn, &db.schema, bootstrap::TX0).unwrap();
+        assert_eq!(transactions.0.len(), 8);
+        assert_eq!(transactions.0[0].0.len(), 80);
+        assert_eq!(transactions.0[1].0.len(), 10);
+        assert_eq!(transactions.0[2].0.len(), 1);
+        assert_eq!(transactions.0[3].0.len(), 13);
+        assert_eq!(transactions.0[4].0.len(), 5);
+        assert_eq!(transactions.0[5].0.len(), 5);
+        assert_eq!(transactions.0[6].0.len(), 3);
+        assert_eq!(transactions.0[7].0.len(), 5);
+
+        // And the upgraded store looks like a freshly created store.
+        let datoms = debug::datoms_after(&conn, &db.schema, 0).unwrap();
+        assert_eq!(datoms.0.len(), 110);
+    }
+
+    /// Open the v2empty fixture and migrate it to version 4, the last version without
+    /// :db.revert/tx.
+    fn open_v2empty_at_v4(label: &str) -> (debug::TempPath, rusqlite::Connection) {
+        let path = copy_fixture("v2empty.db", label);
+        let mut conn = new_connection(&path).expect("Couldn't open db");
+
+        for migration in MIGRATIONS.iter().filter(|migration| migration.from_version >= 2 && migration.from_version < 4) {
+            let tx = conn.transaction().unwrap();
+            migration.apply(&tx).unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(get_user_version(&conn).unwrap(), 4);
+        (path, conn)
+    }
+
+    #[test]
+    fn test_update_with_allocated_db_entids() {
+        let (_path, mut conn) = open_v2empty_at_v4("allocated_db_entids");
+
+        // Install a partition, which allocates entid 38 from :db.part/db.
+        let db = read_db(&conn).unwrap();
+        let value = edn::parse::value(r#"[{:db/id (tempid :db.part/db "p") :db/ident :db.part/myapp}
+                                          [:db/add :db.part/db :db.install/partition "p"]]"#).unwrap().without_spans();
+        let entities = mentat_tx_parser::Tx::parse(&[value][..]).unwrap();
+        let (report, _, _) = transact(&conn, db.partition_map, &db.schema, entities).unwrap();
+        assert_eq!(report.tempids["p"], 38);
+
+        // Version 5 assigns :db.revert/tx a reserved entid, so migrating leaves the partition be.
+        let db = ensure_current_version(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), CURRENT_VERSION);
+        assert_eq!(db.schema.get_entid(&symbols::NamespacedKeyword::new("db.part", "myapp")), Some(38));
+        assert_eq!(db.schema.get_entid(&symbols::NamespacedKeyword::new("db.revert", "tx")), Some(entids::DB_REVERT_TX));
+        assert_eq!(db.partition_map.get(":db.part/db").unwrap().index, 39);
+    }
+
+    #[test]
+    fn test_update_fails_if_bootstrap_entids_in_use() {
+        let (_path, mut conn) = open_v2empty_at_v4("bootstrap_entids_in_use");
+
+        // Use the entid that version 5 assigns to :db.revert/tx.
+        let db = read_db(&conn).unwrap();
+        let value = edn::parse::value(&format!(r#"[[:db/add {} :db/doc "mine"]]"#, entids::DB_REVERT_TX)).unwrap().without_spans();
+        let entities = mentat_tx_parser::Tx::parse(&[value][..]).unwrap();
+        transact(&conn, db.partition_map, &db.schema, entities).unwrap();
+
+        // Migrating fails rather than merge :db.revert/tx into the existing entity, and leaves the
+        // store as it was.
+        match update_from_version(&mut conn, 4).unwrap_err() {
+            Error(ErrorKind::BootstrapEntidsInUse(5, ref entids), _) => assert_eq!(entids, &vec![entids::DB_REVERT_TX]),
+            x => panic!("expected bootstrap entids in use error, got {:?}", x),
+        }
+        assert_eq!(get_user_version(&conn).unwrap(), 4);
+        assert!(ensure_current_version(&mut conn).is_err());
+    }
+
+    #[test]
+    fn test_open_v1tofino() {
+        let path = copy_fixture("v1tofino.db", "open_v1tofino");
+        let mut conn = new_connection(&path).expect("Couldn't open db");
+
+        let db = ensure_current_version(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), CURRENT_VERSION);
+
+        assert_eq!(db.partition_map.get(":db.part/db").unwrap(), &Partition::new(0, 38));
+        assert_eq!(db.partition_map.get(":db.part/user").unwrap(), &Partition::new(0x10000, 65568));
+        assert_eq!(db.partition_map.get(":db.part/tx").unwrap(), &Partition::new(bootstrap::TX0, 268435476));
+
+        // Bootstrap idents and user idents are both present.
+        assert_eq!(db.schema.ident_map.len(), 41 + 15);
+        assert_eq!(db.schema.get_entid(&symbols::NamespacedKeyword::new("db.schema", "attribute")), Some(entids::DB_SCHEMA_ATTRIBUTE));
+
+        let url = db.schema.attribute_for_ident(&symbols::NamespacedKeyword::new("page", "url")).unwrap();
+        assert_eq!(url.value_type, ValueType::String);
+        assert!(url.fulltext);
+        assert!(url.unique_identity);
+
+        let visit_at_entid = db.schema.get_entid(&symbols::NamespacedKeyword::new("visit", "visitAt")).unwrap();
+        let visit_at = db.schema.attribute_for_entid(visit_at_entid).unwrap();
+        assert_eq!(visit_at.value_type, ValueType::Instant);
+
+        // Datomish stored millisecond reals; they're now microsecond instants.
+        let visited: TypedValue = conn.query_row("SELECT v, value_type_tag FROM datoms WHERE e = 65555 AND a = ?",
+                                                 &[&visit_at_entid], |row| TypedValue::from_sql_value_pair(row.get(0), row.get(1))).unwrap().unwrap();
+        assert_eq!(visited, TypedValue::Instant(DateTime::<Utc>::from_micros(1480472296609000).unwrap()));
+
+        // Re-opening doesn't migrate again.
+        let reopened = ensure_current_version(&mut conn).unwrap();
+        assert_eq!(db, reopened);
     }
 
     /// Assert that a sequence of transactions meets expectations.
@@ -909,7 +1977,13 @@ mod tests {
 
             let entities: Vec<_> = mentat_tx_parser::Tx::parse(&[assertions][..]).unwrap();
 
+            // Like `Conn`, roll back transactions that fail part way through.
+            conn.execute_batch("SAVEPOINT assert_transactions").unwrap();
             let maybe_report = transact(&conn, partition_map.clone(), schema, entities);
+            if maybe_report.is_err() {
+                conn.execute_batch("ROLLBACK TO assert_transactions").unwrap();
+            }
+            conn.execute_batch("RELEASE assert_transactions").unwrap();
 
             if let Some(expected_transaction) = expected_transaction {
                 if !expected_transaction.is_nil() {
@@ -926,7 +2000,6 @@ mod tests {
                     continue
                 }
 
-                // TODO: test schema changes in the EDN format.
                 let (report, next_partition_map, next_schema) = maybe_report.unwrap();
                 *partition_map = next_partition_map;
                 if let Some(next_schema) = next_schema {
@@ -964,12 +2037,12 @@ mod tests {
 
         // Does not include :db/txInstant.
         let datoms = debug::datoms_after(&conn, &db.schema, 0).unwrap();
-        assert_eq!(datoms.0.len(), 88);
+        assert_eq!(datoms.0.len(), 110);
 
         // Includes :db/txInstant.
         let transactions = debug::transactions_after(&conn, &db.schema, 0).unwrap();
         assert_eq!(transactions.0.len(), 1);
-        assert_eq!(transactions.0[0].0.len(), 89);
+        assert_eq!(transactions.0[0].0.len(), 111);
 
         // TODO: extract a test macro simplifying this boilerplate yet further.
         let value = edn::parse::value(include_str!("../../tx/fixtures/test_add.edn")).unwrap().without_spans();
@@ -986,12 +2059,12 @@ mod tests {
 
         // Does not include :db/txInstant.
         let datoms = debug::datoms_after(&conn, &db.schema, 0).unwrap();
-        assert_eq!(datoms.0.len(), 88);
+        assert_eq!(datoms.0.len(), 110);
 
         // Includes :db/txInstant.
         let transactions = debug::transactions_after(&conn, &db.schema, 0).unwrap();
         assert_eq!(transactions.0.len(), 1);
-        assert_eq!(transactions.0[0].0.len(), 89);
+        assert_eq!(transactions.0[0].0.len(), 111);
 
         let value = edn::parse::value(include_str!("../../tx/fixtures/test_retract.edn")).unwrap().without_spans();
 
@@ -1006,12 +2079,12 @@ mod tests {
 
         // Does not include :db/txInstant.
         let datoms = debug::datoms_after(&conn, &db.schema, 0).unwrap();
-        assert_eq!(datoms.0.len(), 88);
+        assert_eq!(datoms.0.len(), 110);
 
         // Includes :db/txInstant.
         let transactions = debug::transactions_after(&conn, &db.schema, 0).unwrap();
         assert_eq!(transactions.0.len(), 1);
-        assert_eq!(transactions.0[0].0.len(), 89);
+        assert_eq!(transactions.0[0].0.len(), 111);
 
         let value = edn::parse::value(include_str!("../../tx/fixtures/test_upsert_vector.edn")).unwrap().without_spans();
 
@@ -1019,6 +2092,349 @@ mod tests {
         assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
     }
 
+    #[test]
+    fn test_lookup_refs() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_lookup_refs.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+    }
+
+    #[test]
+    fn test_map_notation() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_map_notation.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+    }
+
+    #[test]
+    fn test_tx_report() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let db = ensure_current_version(&mut conn).unwrap();
+
+        let parse = |s: &str| {
+            let value = edn::parse::value(s).unwrap().without_spans();
+            mentat_tx_parser::Tx::parse(&[value][..]).unwrap()
+        };
+
+        let entities = parse(r#"[[:db/add "t1" :db/ident :test/one]
+                                 [:db/add "t2" :db.schema/attribute "t1"]]"#);
+        let (report, partition_map, _) = transact(&conn, db.partition_map.clone(), &db.schema, entities).unwrap();
+        let tx = report.tx_id;
+
+        let mut tempids = BTreeMap::new();
+        tempids.insert("t1".to_string(), 65536);
+        tempids.insert("t2".to_string(), 65537);
+        assert_eq!(report.tempids, tempids);
+
+        assert_eq!(report.tx_data, vec![
+            Datom { e: 65536, a: entids::DB_IDENT, v: TypedValue::Keyword(symbols::NamespacedKeyword::new("test", "one")), tx: tx, added: true },
+            Datom { e: 65537, a: entids::DB_SCHEMA_ATTRIBUTE, v: TypedValue::Ref(65536), tx: tx, added: true },
+            Datom { e: tx, a: entids::DB_TX_INSTANT, v: TypedValue::Instant(report.tx_instant), tx: tx, added: true },
+        ]);
+
+        // Upserted tempids are reported; internal tempids are not.  Data that doesn't change the
+        // store is not reported.
+        let entities = parse(r#"[[:db/add "u" :db/ident :test/one]
+                                 [:db/add "u" :db.schema/attribute 100]
+                                 [:db/retract 65537 :db.schema/attribute 65536]
+                                 [:db/retract 65537 :db.schema/attribute 65538]
+                                 {:db/ident :test/two}]"#);
+        let (report, _, _) = transact(&conn, partition_map, &db.schema, entities).unwrap();
+        let tx = report.tx_id;
+
+        let mut tempids = BTreeMap::new();
+        tempids.insert("u".to_string(), 65536);
+        assert_eq!(report.tempids, tempids);
+
+        assert_eq!(report.tx_data, vec![
+            Datom { e: 65536, a: entids::DB_SCHEMA_ATTRIBUTE, v: TypedValue::Ref(100), tx: tx, added: true },
+            Datom { e: 65537, a: entids::DB_SCHEMA_ATTRIBUTE, v: TypedValue::Ref(65536), tx: tx, added: false },
+            Datom { e: 65538, a: entids::DB_IDENT, v: TypedValue::Keyword(symbols::NamespacedKeyword::new("test", "two")), tx: tx, added: true },
+            Datom { e: tx, a: entids::DB_TX_INSTANT, v: TypedValue::Instant(report.tx_instant), tx: tx, added: true },
+        ]);
+    }
+
+    #[test]
+    fn test_schema() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_schema.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        let ident = symbols::NamespacedKeyword::new("person", "name");
+        let entid = db.schema.get_entid(&ident).unwrap();
+        assert_eq!(db.schema.attribute_for_entid(entid), Some(&Attribute {
+            value_type: ValueType::String,
+            unique_value: true,
+            unique_identity: true,
+            index: true,
+            ..Default::default()
+        }));
+
+        // The materialized views agree with the in-memory schema.
+        assert_eq!(read_db(&conn).unwrap().schema, db.schema);
+    }
+
+    #[test]
+    fn test_update_idents_and_schema() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let db = ensure_current_version(&mut conn).unwrap();
+
+        let mut partition_map = db.partition_map;
+        let mut schema = db.schema;
+        for assertions in &[r#"[{:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+                                {:db/ident :person/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}]"#,
+                            r#"[[:db/add :person/name :db/ident :person/full-name]
+                                [:db/add :person/age :db/index true]]"#] {
+            let value = edn::parse::value(assertions).unwrap().without_spans();
+            let entities = mentat_tx_parser::Tx::parse(&[value][..]).unwrap();
+            let (_report, next_partition_map, next_schema) = transact(&conn, partition_map, &schema, entities).unwrap();
+            partition_map = next_partition_map;
+            schema = next_schema.unwrap();
+        }
+
+        // Only the changed entities' rows were replaced, and they agree with a complete rebuild.
+        assert_eq!(read_db(&conn).unwrap().schema, schema);
+        assert!(schema.get_entid(&symbols::NamespacedKeyword::new("person", "name")).is_none());
+        assert!(schema.get_entid(&symbols::NamespacedKeyword::new("person", "full-name")).is_some());
+
+        rebuild_idents_and_schema(&conn).unwrap();
+        assert_eq!(read_db(&conn).unwrap().schema, schema);
+    }
+
+    #[test]
+    fn test_schema_alteration() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_schema_alteration.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        // Existing datoms were rewritten with the altered attributes' flags.
+        let count = |s: &str| -> i64 { conn.query_row(s, &[], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM datoms WHERE a = 101 AND index_avet AND unique_value"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM datoms WHERE a = 100 AND index_avet AND NOT unique_value"), 2);
+    }
+
+    #[test]
+    fn test_tx_metadata() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let db = ensure_current_version(&mut conn).unwrap();
+
+        let parse = |s: &str| {
+            let value = edn::parse::value(s).unwrap().without_spans();
+            mentat_tx_parser::Tx::parse(&[value][..]).unwrap()
+        };
+
+        // The transaction entity is named by :db/tx or the reserved tempid "datomic.tx".
+        let entities = parse(r#"[[:db/add :db/tx :db/doc "import"]
+                                 [:db/add "datomic.tx" :db.schema/attribute :db/tx]]"#);
+        let (report, partition_map, _) = transact(&conn, db.partition_map.clone(), &db.schema, entities).unwrap();
+        let tx = report.tx_id;
+
+        assert!(report.tempids.is_empty());
+        assert_eq!(report.tx_data, vec![
+            Datom { e: tx, a: entids::DB_TX_INSTANT, v: TypedValue::Instant(report.tx_instant), tx: tx, added: true },
+            Datom { e: tx, a: entids::DB_DOC, v: TypedValue::String("import".to_string()), tx: tx, added: true },
+            Datom { e: tx, a: entids::DB_SCHEMA_ATTRIBUTE, v: TypedValue::Ref(tx), tx: tx, added: true },
+        ]);
+
+        // A caller-supplied :db/txInstant replaces the transactor's.
+        let instant = report.tx_instant + Duration::milliseconds(1);
+        let entities = parse(&format!("[[:db/add :db/tx :db/txInstant {}]]", edn::Value::Instant(instant)));
+        let (report, partition_map, _) = transact(&conn, partition_map, &db.schema, entities).unwrap();
+        let tx = report.tx_id;
+
+        assert_eq!(report.tx_instant, instant);
+        assert_eq!(report.tx_data, vec![
+            Datom { e: tx, a: entids::DB_TX_INSTANT, v: TypedValue::Instant(instant), tx: tx, added: true },
+        ]);
+
+        // But it must not precede the previous transaction's.
+        let entities = parse(&format!("[[:db/add :db/tx :db/txInstant {}]]", edn::Value::Instant(instant - Duration::microseconds(1))));
+        match transact(&conn, partition_map.clone(), &db.schema, entities).unwrap_err() {
+            Error(ErrorKind::BadTxInstant(_), _) => { },
+            x => panic!("expected bad :db/txInstant error, got {:?}", x),
+        }
+
+        // And it can only be asserted for the transaction entity.
+        let entities = parse(&format!("[[:db/add 100 :db/txInstant {}]]", edn::Value::Instant(instant)));
+        match transact(&conn, partition_map.clone(), &db.schema, entities).unwrap_err() {
+            Error(ErrorKind::BadTxInstant(_), _) => { },
+            x => panic!("expected bad :db/txInstant error, got {:?}", x),
+        }
+
+        // The transactor's own :db/txInstant doesn't precede the previous transaction's either,
+        // even if the clock is behind it.
+        let future = instant + Duration::seconds(1000);
+        let entities = parse(&format!("[[:db/add :db/tx :db/txInstant {}]]", edn::Value::Instant(future)));
+        let (_, partition_map, _) = transact(&conn, partition_map, &db.schema, entities).unwrap();
+        let (report, _, _) = transact(&conn, partition_map, &db.schema, parse("[]")).unwrap();
+        assert_eq!(report.tx_instant, future);
+        assert_eq!(read_last_tx_instant(&conn).unwrap(), Some(future));
+    }
+
+    #[test]
+    fn test_partitions() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_partitions.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        // The installed partition is contiguous, above the bootstrap partitions, and persisted.
+        assert_eq!(db.partition_map.get(":db.part/myapp").unwrap(), &Partition::new(INSTALLED_PARTITION_SIZE, INSTALLED_PARTITION_SIZE + 2));
+        assert_eq!(read_partition_map(&conn).unwrap(), db.partition_map);
+    }
+
+    #[test]
+    fn test_db_partition_exhausted() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        // The last entid before the reserved range can be allocated ...
+        db.partition_map.get_mut(":db.part/db").unwrap().index = entids::BOOTSTRAP_RESERVED_START - 1;
+        let value = edn::parse::value(r#"[{:db/id (tempid :db.part/db "p") :db/ident :db.part/last}]"#).unwrap().without_spans();
+        let entities = mentat_tx_parser::Tx::parse(&[value][..]).unwrap();
+        let (report, next_partition_map, _) = transact(&conn, db.partition_map, &db.schema, entities).unwrap();
+        assert_eq!(report.tempids["p"], entids::BOOTSTRAP_RESERVED_START - 1);
+
+        // ... but the reserved entids can't.
+        let value = edn::parse::value(r#"[{:db/id (tempid :db.part/db "p") :db/ident :db.part/reserved}]"#).unwrap().without_spans();
+        let entities = mentat_tx_parser::Tx::parse(&[value][..]).unwrap();
+        match transact(&conn, next_partition_map, &db.schema, entities).unwrap_err() {
+            Error(ErrorKind::PartitionExhausted(ref part), _) => assert_eq!(part, ":db.part/db"),
+            x => panic!("expected partition exhausted error, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_no_history() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_no_history.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        // The replaced and retracted values were forgotten entirely.
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM transactions WHERE a = 100", &[], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_bytes.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+    }
+
+    #[test]
+    fn test_doubles() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_doubles.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+    }
+
+    #[test]
+    fn test_excise() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_excise.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        // No history remains, and the excised fulltext value is collected.
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM transactions WHERE e = 200", &[], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM fulltext_values", &[], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_tx_range() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_fulltext.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        let entries: Vec<TxLogEntry> = tx_range(&conn, &db.schema, bootstrap::TX0 + 2, bootstrap::TX0 + 4).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.tx_id).collect::<Vec<_>>(),
+                   vec![bootstrap::TX0 + 2, bootstrap::TX0 + 3]);
+
+        // Fulltext values are resolved, and attributes are named by their idents.
+        let fulltext = symbols::NamespacedKeyword::new("test", "fulltext");
+        let tx_instant = symbols::NamespacedKeyword::new("db", "txInstant");
+        assert_eq!(entries[0].tx_data,
+                   vec![(200, fulltext.clone(), TypedValue::String("test this".to_string()), true),
+                        (201, fulltext.clone(), TypedValue::String("test that".to_string()), true),
+                        (bootstrap::TX0 + 2, tx_instant.clone(), TypedValue::Instant(entries[0].tx_instant), true)]);
+        assert_eq!(entries[1].tx_data,
+                   vec![(202, fulltext.clone(), TypedValue::String("test this".to_string()), true),
+                        (bootstrap::TX0 + 3, tx_instant.clone(), TypedValue::Instant(entries[1].tx_instant), true)]);
+
+        // An open range reads through the end of the log.
+        let count = tx_range(&conn, &db.schema, bootstrap::TX0 + 1, None).count();
+        let expected: i64 = conn.query_row("SELECT COUNT(DISTINCT tx) FROM transactions WHERE tx > ?", &[&bootstrap::TX0], |row| row.get(0)).unwrap();
+        assert_eq!(count as i64, expected);
+    }
+
+    #[test]
+    fn test_fulltext() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_fulltext.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+
+        // Values are interned once each; the retracted value that was never present is collected.
+        let mut stmt = conn.prepare("SELECT text FROM fulltext_values ORDER BY text").unwrap();
+        let texts: Vec<String> = stmt.query_and_then(&[], |row| row.get_checked(0)).unwrap().collect::<::std::result::Result<_, _>>().unwrap();
+        assert_eq!(texts, vec!["one", "test that", "test the other", "test this", "two"]);
+    }
+
+    #[test]
+    fn test_tx_functions() {
+        let mut conn = new_connection("").expect("Couldn't open in-memory db");
+        let mut db = ensure_current_version(&mut conn).unwrap();
+
+        let value = edn::parse::value(include_str!("../../tx/fixtures/test_tx_functions.edn")).unwrap().without_spans();
+
+        let transactions = value.as_vector().unwrap();
+        assert_transactions(&conn, &mut db.partition_map, &mut db.schema, transactions);
+    }
+
     #[test]
     fn test_sqlite_limit() {
         let conn = new_connection("").expect("Couldn't open in-memory db");
@@ -14,7 +14,12 @@
 
 use std::borrow::Borrow;
 use std::collections::{BTreeSet};
+use std::env;
+use std::fs;
 use std::io::{Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use itertools::Itertools;
 use rusqlite;
@@ -122,7 +127,7 @@ pub fn datoms<S: Borrow<Schema>>(conn: &rusqlite::Connection, schema: &S) -> Res
 ///
 /// The datom set returned does not include any datoms of the form [... :db/txInstant ...].
 pub fn datoms_after<S: Borrow<Schema>>(conn: &rusqlite::Connection, schema: &S, tx: i64) -> Result<Datoms> {
-    let mut stmt: rusqlite::Statement = conn.prepare("SELECT e, a, v, value_type_tag, tx FROM datoms WHERE tx > ? ORDER BY e ASC, a ASC, v ASC, tx ASC")?;
+    let mut stmt: rusqlite::Statement = conn.prepare("SELECT e, a, v, value_type_tag, tx FROM all_datoms WHERE tx > ? ORDER BY e ASC, a ASC, v ASC, tx ASC")?;
 
     let r: Result<Vec<_>> = stmt.query_and_then(&[&tx], |row| {
         let e: i64 = row.get_checked(0)?;
@@ -158,7 +163,13 @@ pub fn datoms_after<S: Borrow<Schema>>(conn: &rusqlite::Connection, schema: &S,
 ///
 /// Each transaction returned includes the [:db/tx :db/txInstant ...] datom.
 pub fn transactions_after<S: Borrow<Schema>>(conn: &rusqlite::Connection, schema: &S, tx: i64) -> Result<Transactions> {
-    let mut stmt: rusqlite::Statement = conn.prepare("SELECT e, a, v, value_type_tag, tx, added FROM transactions WHERE tx > ? ORDER BY tx ASC, e ASC, a ASC, v ASC, added ASC")?;
+    let mut stmt: rusqlite::Statement = conn.prepare(r#"
+      SELECT t.e, t.a, coalesce(f.text, t.v), t.value_type_tag, t.tx, t.added
+      FROM transactions AS t
+      LEFT JOIN fulltext_values AS f
+      ON t.value_type_tag = 10 AND typeof(t.v) = 'integer' AND f.rowid = t.v
+      WHERE t.tx > ?
+      ORDER BY t.tx ASC, t.e ASC, t.a ASC, t.v ASC, t.added ASC"#)?;
 
     let r: Result<Vec<_>> = stmt.query_and_then(&[&tx], |row| {
         let e: i64 = row.get_checked(0)?;
@@ -217,3 +228,39 @@ pub fn dump_sql_query(conn: &rusqlite::Connection, sql: &str, params: &[&ToSql])
     let dump = String::from_utf8(tw.into_inner().unwrap()).unwrap();
     Ok(dump)
 }
+
+/// A unique path for an on-disk test store, removed (along with any SQLite journal files) when
+/// dropped.  Declare it before any connections to the store so that they are closed first.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(label: &str) -> TempPath {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("mentat_test_{}_{}_{}.db", label, process::id(), n));
+        let temp = TempPath(path);
+        temp.remove();
+        temp
+    }
+
+    fn remove(&self) {
+        let _ = fs::remove_file(&self.0);
+        for suffix in &["-journal", "-wal", "-shm"] {
+            let mut name = self.0.clone().into_os_string();
+            name.push(suffix);
+            let _ = fs::remove_file(name);
+        }
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
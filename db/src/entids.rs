@@ -56,3 +56,36 @@ pub const DB_DOC: Entid = 35;
 // Added in SQL schema v2.
 pub const DB_SCHEMA_VERSION: Entid = 36;
 pub const DB_SCHEMA_ATTRIBUTE: Entid = 37;
+
+/// Bootstrap idents added after SQL schema v2 are assigned entids from the end of :db.part/db,
+/// starting here, so that they can't collide with entids that a store has already allocated from
+/// :db.part/db.  Transactions can't allocate entids from this range.
+pub const BOOTSTRAP_RESERVED_START: Entid = 0xff00;
+
+// Added in SQL schema v5.
+pub const DB_REVERT_TX: Entid = 0xff00;
+
+// Added in SQL schema v7.
+pub const DB_TYPE_UUID: Entid = 0xff01;
+pub const DB_TYPE_URI: Entid = 0xff02;
+
+// Added in SQL schema v8.
+pub const DB_INDEX_BYTES: Entid = 0xff03;
+
+/// Return `false` if the given attribute will not change the metadata: recognized idents and
+/// schema.
+pub fn might_update_metadata(attribute: Entid) -> bool {
+    match attribute {
+        DB_IDENT |
+        DB_VALUE_TYPE |
+        DB_CARDINALITY |
+        DB_UNIQUE |
+        DB_IS_COMPONENT |
+        DB_INDEX |
+        DB_FULLTEXT |
+        DB_NO_HISTORY |
+        DB_INDEX_BYTES |
+        DB_DOC => true,
+        _ => false,
+    }
+}
@@ -13,7 +13,7 @@
 use edn;
 use rusqlite;
 
-use types::{Entid, ValueType};
+use types::{Entid, TypedValue, ValueType};
 
 error_chain! {
     types {
@@ -51,6 +51,13 @@ error_chain! {
             display("bad SQL store user_version: {}", version)
         }
 
+        /// A migration step can't assign entids to its new bootstrap idents, because the store has
+        /// already allocated or used those entids.
+        BootstrapEntidsInUse(version: i32, entids: Vec<Entid>) {
+            description("bootstrap entids already in use")
+            display("cannot migrate to store version {}: bootstrap entids {:?} are already in use", version, entids)
+        }
+
         /// A bootstrap definition couldn't be parsed or installed.  This is a programmer error, not
         /// a runtime error.
         BadBootstrapDefinition(t: String) {
@@ -64,6 +71,12 @@ error_chain! {
             display("bad schema assertion: '{}'", t)
         }
 
+        /// A schema alteration isn't valid for the existing datoms of the altered attribute.
+        SchemaAlterationFailed(ident: String, t: String) {
+            description("schema alteration failed")
+            display("schema alteration failed for {}: {}", ident, t)
+        }
+
         /// An ident->entid mapping failed.
         UnrecognizedIdent(ident: String) {
             description("no entid found for ident")
@@ -75,5 +88,97 @@ error_chain! {
             description("no ident found for entid")
             display("no ident found for entid: '{}'", entid)
         }
+
+        /// An excision named an entity that can't be excised.
+        ExcisionFailed(entity: String, t: String) {
+            description("excision failed")
+            display("excision failed for {}: {}", entity, t)
+        }
+
+        /// A transaction can't be reverted because later transactions changed some of the same
+        /// [e a] pairs.
+        RevertConflict(tx: Entid, conflicts: Vec<(Entid, String)>) {
+            description("transaction can't be reverted")
+            display("transaction {} can't be reverted; later transactions changed [e a] pairs {:?}", tx, conflicts)
+        }
+
+        /// A transaction can't be reverted because it changed :db/noHistory attributes, whose
+        /// replaced values the log doesn't remember.
+        RevertNoHistory(tx: Entid, attributes: Vec<String>) {
+            description("transaction can't be reverted")
+            display("transaction {} can't be reverted; it changed :db/noHistory attributes {:?}", tx, attributes)
+        }
+
+        /// A caller-supplied :db/txInstant was not valid for the transaction.
+        BadTxInstant(t: String) {
+            description("bad :db/txInstant")
+            display("bad :db/txInstant: {}", t)
+        }
+
+        /// A tempid named a partition that isn't installed.
+        UnrecognizedPartition(part: String) {
+            description("no partition found")
+            display("no partition found: '{}'", part)
+        }
+
+        /// A partition has no more entids that transactions can allocate.
+        PartitionExhausted(part: String) {
+            description("partition exhausted")
+            display("partition '{}' has no more entids to allocate", part)
+        }
+
+        /// A tempid was named with more than one partition in a single transaction.
+        ConflictingTempIdPartitions(tempid: String, part: String, other_part: String) {
+            description("tempid allocated from more than one partition")
+            display("tempid {} is allocated from both {} and {}", tempid, part, other_part)
+        }
+
+        /// A map notation entity couldn't be expanded into assertions.
+        BadMapNotation(t: String) {
+            description("bad map notation entity")
+            display("bad map notation entity: {}", t)
+        }
+
+        /// A :db.fn/cas [e a old new] found a current value other than `old`.
+        CasFailed(e: Entid, attribute: String, expected: Option<TypedValue>, actual: Option<TypedValue>) {
+            description("compare-and-swap failed")
+            display("compare-and-swap failed for [{} {}]: expected {:?} but found {:?}", e, attribute, expected, actual)
+        }
+
+        /// A tempid upserted to more than one distinct entid.
+        ConflictingUpsert(tempid: String, entids: Vec<String>) {
+            description("conflicting upsert")
+            display("conflicting upsert: tempid {} resolves to more than one entid: {}", tempid, entids.join(", "))
+        }
+
+        /// An [a v] pair for a :db/unique attribute was asserted for more than one entity.
+        UniqueValueViolation(attribute: String, value: TypedValue, existing_entid: String) {
+            description("unique value violation")
+            display("unique value violation: [{} {:?}] is already asserted for entity {}", attribute, value, existing_entid)
+        }
+
+        /// More than one value was asserted for a :db.cardinality/one attribute of an entity.
+        CardinalityConflict(entity: String, attribute: String, values: Vec<TypedValue>) {
+            description("cardinality conflict")
+            display("cardinality conflict: more than one value asserted for [{} {}]: {:?}", entity, attribute, values)
+        }
+
+        /// A lookup-ref [a v] named an attribute that is not :db/unique.
+        LookupRefAttributeNotUnique(attribute: String, value: TypedValue) {
+            description("lookup-ref attribute is not :db/unique")
+            display("lookup-ref attribute is not :db/unique: [{} {:?}]", attribute, value)
+        }
+
+        /// A lookup-ref [a v] didn't match any [e a v] datom in the store.
+        UnresolvedLookupRef(attribute: String, value: TypedValue) {
+            description("no entid found for lookup-ref")
+            display("no entid found for lookup-ref: [{} {:?}]", attribute, value)
+        }
+
+        /// A :db/fulltext attribute was given a value that isn't a :db.type/string.
+        BadFulltextValue(value: TypedValue) {
+            description("fulltext value is not a string")
+            display("fulltext value is not a string: {:?}", value)
+        }
     }
 }
@@ -14,14 +14,17 @@
 
 use std;
 use std::collections::HashMap;
+use std::fmt;
 use std::rc::Rc;
 
 use errors;
 use errors::ErrorKind;
+use schema::SchemaBuilding;
 use types::{
     AVMap,
     AVPair,
     Entid,
+    Schema,
     TypedValue,
 };
 use mentat_tx::entities::OpType;
@@ -34,9 +37,30 @@ pub enum Term<E, V> {
 pub type EntidOr<T> = std::result::Result<Entid, T>;
 pub type TypedValueOr<T> = std::result::Result<TypedValue, T>;
 
-pub type TempId = Rc<String>;
+/// A tempid is either named in the transaction (`External`) or allocated by the transactor while
+/// expanding map notation (`Internal`).  Internal tempids never collide with external tempids.
+#[derive(Clone,Debug,Eq,Hash,Ord,PartialOrd,PartialEq)]
+pub enum TempIdName {
+    External(String),
+    Internal(i64),
+}
+
+impl fmt::Display for TempIdName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &TempIdName::External(ref s) => write!(f, "{}", s),
+            &TempIdName::Internal(x) => write!(f, "<tempid {}>", x),
+        }
+    }
+}
+
+pub type TempId = Rc<TempIdName>;
 pub type TempIdMap = HashMap<TempId, Entid>;
 
+/// Map tempids to the partitions they are allocated from.  Tempids not in the map are allocated
+/// from :db.part/user.
+pub type TempIdPartitionMap = HashMap<TempId, String>;
+
 pub type LookupRef = Rc<AVPair>;
 
 /// Internal representation of an entid on its way to resolution.  We either have the simple case (a
@@ -66,7 +90,8 @@ impl TermWithTempIds {
 }
 
 /// Given an `EntidOr` or a `TypedValueOr`, replace any internal `LookupRef` with the entid from
-/// the given map.  Fail if any `LookupRef` cannot be replaced.
+/// the given map.  Fail if any `LookupRef` cannot be replaced; the `schema` is used to name the
+/// offending lookup-ref.
 ///
 /// `lift` allows to specify how the entid found is mapped into the output type.  (This could
 /// also be an `Into` or `From` requirement.)
@@ -74,16 +99,22 @@ impl TermWithTempIds {
 /// The reason for this awkward expression is that we're parameterizing over the _type constructor_
 /// (`EntidOr` or `TypedValueOr`), which is not trivial to express in Rust.  This only works because
 /// they're both the same `Result<...>` type with different parameterizations.
-pub fn replace_lookup_ref<T, U>(lookup_map: &AVMap, desired_or: Result<T, LookupRefOrTempId>, lift: U) -> errors::Result<Result<T, TempId>> where U: FnOnce(Entid) -> T {
+pub fn replace_lookup_ref<T, U>(schema: &Schema, lookup_map: &AVMap, desired_or: Result<T, LookupRefOrTempId>, lift: U) -> errors::Result<Result<T, TempId>> where U: FnOnce(Entid) -> T {
     match desired_or {
         Ok(desired) => Ok(Ok(desired)), // N.b., must unwrap here -- the ::Ok types are different!
         Err(other) => {
             match other {
                 LookupRefOrTempId::TempId(t) => Ok(Err(t)),
-                LookupRefOrTempId::LookupRef(av) => lookup_map.get(&*av)
-                    .map(|x| lift(*x)).map(Ok)
-                    // XXX TODO: fix this error kind!
-                    .ok_or_else(|| ErrorKind::UnrecognizedIdent(format!("couldn't lookup [a v]: {:?}", (*av).clone())).into()),
+                LookupRefOrTempId::LookupRef(av) => {
+                    match lookup_map.get(&*av) {
+                        Some(x) => Ok(Ok(lift(*x))),
+                        None => {
+                            let &(a, ref v) = &*av;
+                            let attribute = schema.require_ident(a)?.to_string();
+                            bail!(ErrorKind::UnresolvedLookupRef(attribute, v.clone()))
+                        },
+                    }
+                },
             }
         }
     }
@@ -8,13 +8,17 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+// The `error_chain!` in `errors` defines enough error kinds to need a deeper macro recursion limit.
+#![recursion_limit = "256"]
+
+extern crate chrono;
 #[macro_use]
 extern crate error_chain;
 extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
 extern crate rusqlite;
-extern crate time;
+extern crate uuid;
 
 extern crate tabwriter;
 
@@ -24,14 +28,16 @@ extern crate mentat_core;
 extern crate mentat_tx;
 extern crate mentat_tx_parser;
 
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
+use mentat_core::truncate_to_micros;
 use std::iter::repeat;
 pub use errors::{Error, ErrorKind, ResultExt, Result};
 
 pub mod db;
 mod bootstrap;
 pub mod debug;
-mod entids;
+pub mod entids;
 pub mod errors;
 mod schema;
 mod types;
@@ -42,13 +48,17 @@ mod tx;
 
 pub use db::{
     TypedSQLValue,
+    TxRange,
     new_connection,
+    tx_range,
 };
 
 pub use tx::transact;
 pub use types::{
+    Datom,
     DB,
     PartitionMap,
+    TxLogEntry,
     TxReport,
 };
 
@@ -88,10 +98,10 @@ pub fn repeat_values(values_per_tuple: usize, tuples: usize) -> String {
     values
 }
 
-/// Return the current time in milliseconds after the Unix epoch according to the local clock.
+/// Return the current time according to the local clock, truncated to the microsecond precision
+/// that we store.
 ///
 /// Compare `Date.now()` in JavaScript, `System.currentTimeMillis` in Java.
-pub fn now() -> i64 {
-    let now = time::get_time();
-    (now.sec as i64 * 1_000) + (now.nsec as i64 / (1_000_000))
+pub fn now() -> DateTime<Utc> {
+    truncate_to_micros(Utc::now())
 }
@@ -44,6 +44,12 @@ fn validate_schema_map(entid_map: &EntidMap, schema_map: &SchemaMap) -> Result<(
         if attribute.component && attribute.value_type != ValueType::Ref {
             bail!(ErrorKind::BadSchemaAssertion(format!(":db/isComponent true without :db/valueType :db.type/ref for entid: {}", ident)))
         }
+        if attribute.index && attribute.value_type == ValueType::Bytes && !attribute.index_bytes {
+            bail!(ErrorKind::BadSchemaAssertion(format!(":db/index true or :db/unique with :db/valueType :db.type/bytes without :db/indexBytes true for entid: {}", ident)))
+        }
+        if attribute.index_bytes && attribute.value_type != ValueType::Bytes {
+            bail!(ErrorKind::BadSchemaAssertion(format!(":db/indexBytes true without :db/valueType :db.type/bytes for entid: {}", ident)))
+        }
         // TODO: consider warning if we have :db/index true for :db/valueType :db.type/string,
         // since this may be inefficient.  More generally, we should try to drive complex
         // :db/valueType (string, uri, json in the future) users to opt-in to some hash-indexing
@@ -103,8 +109,13 @@ impl SchemaBuilding for Schema {
                         TypedValue::Ref(entids::DB_TYPE_REF) => { attributes.value_type = ValueType::Ref; },
                         TypedValue::Ref(entids::DB_TYPE_BOOLEAN) => { attributes.value_type = ValueType::Boolean; },
                         TypedValue::Ref(entids::DB_TYPE_LONG) => { attributes.value_type = ValueType::Long; },
+                        TypedValue::Ref(entids::DB_TYPE_INSTANT) => { attributes.value_type = ValueType::Instant; },
+                        TypedValue::Ref(entids::DB_TYPE_DOUBLE) => { attributes.value_type = ValueType::Double; },
                         TypedValue::Ref(entids::DB_TYPE_STRING) => { attributes.value_type = ValueType::String; },
                         TypedValue::Ref(entids::DB_TYPE_KEYWORD) => { attributes.value_type = ValueType::Keyword; },
+                        TypedValue::Ref(entids::DB_TYPE_UUID) => { attributes.value_type = ValueType::Uuid; },
+                        TypedValue::Ref(entids::DB_TYPE_URI) => { attributes.value_type = ValueType::Uri; },
+                        TypedValue::Ref(entids::DB_TYPE_BYTES) => { attributes.value_type = ValueType::Bytes; },
                         _ => bail!(ErrorKind::BadSchemaAssertion(format!("Expected [... :db/valueType :db.type/*] but got [... :db/valueType {:?}] for ident '{}' and attribute '{}'", value, ident, attr)))
                     }
                 },
@@ -158,6 +169,20 @@ impl SchemaBuilding for Schema {
                     }
                 },
 
+                entids::DB_NO_HISTORY => {
+                    match *value {
+                        TypedValue::Boolean(x) => { attributes.no_history = x },
+                        _ => bail!(ErrorKind::BadSchemaAssertion(format!("Expected [... :db/noHistory true|false] but got [... :db/noHistory {:?}]", value)))
+                    }
+                },
+
+                entids::DB_INDEX_BYTES => {
+                    match *value {
+                        TypedValue::Boolean(x) => { attributes.index_bytes = x },
+                        _ => bail!(ErrorKind::BadSchemaAssertion(format!("Expected [... :db/indexBytes true|false] but got [... :db/indexBytes {:?}]", value)))
+                    }
+                },
+
                 entids::DB_DOC => {
                     // Nothing for now.
                 },
@@ -199,8 +224,15 @@ impl SchemaTypeChecking for Schema {
                 (&ValueType::Boolean, tv @ TypedValue::Boolean(_)) => Ok(tv),
                 (&ValueType::Long, tv @ TypedValue::Long(_)) => Ok(tv),
                 (&ValueType::Double, tv @ TypedValue::Double(_)) => Ok(tv),
+                (&ValueType::Instant, tv @ TypedValue::Instant(_)) => Ok(tv),
                 (&ValueType::String, tv @ TypedValue::String(_)) => Ok(tv),
                 (&ValueType::Keyword, tv @ TypedValue::Keyword(_)) => Ok(tv),
+                (&ValueType::Uuid, tv @ TypedValue::Uuid(_)) => Ok(tv),
+                (&ValueType::Bytes, tv @ TypedValue::Bytes(_)) => Ok(tv),
+                // URIs have no EDN representation of their own, so we accept strings.
+                (&ValueType::Uri, TypedValue::String(x)) => Ok(TypedValue::Uri(x)),
+                // Integral values are accepted for doubles, so that `5` can stand in for `5.0`.
+                (&ValueType::Double, TypedValue::Long(x)) => Ok(TypedValue::Double((x as f64).into())),
                 // Ref coerces a little: we interpret some things depending on the schema as a Ref.
                 (&ValueType::Ref, TypedValue::Long(x)) => Ok(TypedValue::Ref(x)),
                 (&ValueType::Ref, TypedValue::Keyword(ref x)) => self.require_entid(&x).map(|entid| TypedValue::Ref(entid)),
@@ -47,24 +47,36 @@
 
 use std;
 use std::borrow::Cow;
-use std::collections::BTreeSet;
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+    HashMap,
+};
 
 use ::{to_namespaced_keyword};
+use chrono::{DateTime, Utc};
 use db;
 use db::{
     MentatStoring,
     PartitionMapping,
 };
+use edn;
+use edn::symbols::NamespacedKeyword;
 use entids;
 use errors::{ErrorKind, Result};
 use internal_types::{
+    EntidOr,
+    LookupRef,
     LookupRefOrTempId,
     TempId,
+    TempIdName,
     TempIdMap,
+    TempIdPartitionMap,
     Term,
     TermWithTempIdsAndLookupRefs,
     TermWithTempIds,
     TermWithoutTempIds,
+    TypedValueOr,
     replace_lookup_ref};
 use mentat_core::{
     intern_set,
@@ -72,6 +84,7 @@ use mentat_core::{
 };
 use mentat_tx::entities as entmod;
 use mentat_tx::entities::{Entity, OpType};
+use mentat_tx_parser;
 use rusqlite;
 use schema::{
     SchemaBuilding,
@@ -81,6 +94,7 @@ use types::{
     Attribute,
     AVPair,
     AVMap,
+    Datom,
     Entid,
     PartitionMap,
     TypedValue,
@@ -89,6 +103,9 @@ use types::{
 };
 use upsert_resolution::Generation;
 
+/// The reserved tempid naming the transaction entity, like `[:db/add "datomic.tx" a v]`.
+pub const TX_TEMPID: &'static str = "datomic.tx";
+
 /// A transaction on its way to being applied.
 #[derive(Debug)]
 pub struct Tx<'conn, 'a> {
@@ -111,13 +128,12 @@ pub struct Tx<'conn, 'a> {
 
     /// The timestamp when the transaction began to be committed.
     ///
-    /// This is milliseconds after the Unix epoch according to the transactor's local clock.
-    // TODO: :db.type/instant.
-    tx_instant: i64,
+    /// This is according to the transactor's local clock, with microsecond precision.
+    tx_instant: DateTime<Utc>,
 }
 
 impl<'conn, 'a> Tx<'conn, 'a> {
-    pub fn new(store: &'conn rusqlite::Connection, partition_map: PartitionMap, schema: &'a Schema, tx_id: Entid, tx_instant: i64) -> Tx<'conn, 'a> {
+    pub fn new(store: &'conn rusqlite::Connection, partition_map: PartitionMap, schema: &'a Schema, tx_id: Entid, tx_instant: DateTime<Utc>) -> Tx<'conn, 'a> {
         Tx {
             store: store,
             partition_map: partition_map,
@@ -127,6 +143,36 @@ impl<'conn, 'a> Tx<'conn, 'a> {
         }
     }
 
+    /// Describe the given entid for error messages: its ident, if it has one, or the entid itself.
+    fn describe_entid(&self, e: Entid) -> String {
+        self.schema.get_ident(e).map_or_else(|| e.to_string(), |ident| ident.to_string())
+    }
+
+    /// Excise the entity `e` as described by the excision request entity `request` in `tx_data`:
+    /// the optional `:db.excise/attrs`, `:db.excise/beforeT` and `:db.excise/before` of the request
+    /// narrow what is removed.  Only data from transactions before this one is ever removed.
+    fn excise(&self, tx_data: &[Datom], request: Entid, e: Entid) -> Result<()> {
+        // Excising schema, idents in :db.part/db, or transactions would leave the store unusable.
+        let in_part = |part: &str| self.partition_map.get(part).map_or(false, |p| p.start <= e && e < p.index);
+        if self.schema.is_attribute(e) || in_part(":db.part/db") || in_part(":db.part/tx") {
+            bail!(ErrorKind::ExcisionFailed(self.describe_entid(e), "cannot excise schema, :db.part/db, or transactions".to_string()));
+        }
+
+        let mut attributes = vec![];
+        let mut before_tx = self.tx_id;
+        let mut before_instant = None;
+        for datom in tx_data.iter().filter(|datom| datom.e == request && datom.added) {
+            match (datom.a, &datom.v) {
+                (entids::DB_EXCISE_ATTRS, &TypedValue::Ref(a)) => attributes.push(a),
+                (entids::DB_EXCISE_BEFORE_T, &TypedValue::Long(t)) => before_tx = ::std::cmp::min(before_tx, t),
+                (entids::DB_EXCISE_BEFORE, &TypedValue::Instant(instant)) => before_instant = Some(instant),
+                _ => (),
+            }
+        }
+
+        db::excise(self.store, e, &attributes[..], before_tx, before_instant)
+    }
+
     /// Given a collection of tempids and the [a v] pairs that they might upsert to, resolve exactly
     /// which [a v] pairs do upsert to entids, and map each tempid that upserts to the upserted
     /// entid.  The keys of the resulting map are exactly those tempids that upserted.
@@ -144,80 +190,356 @@ impl<'conn, 'a> Tx<'conn, 'a> {
         // Lookup in the store.
         let av_map: AVMap = self.store.resolve_avs(&av_pairs[..])?;
 
-        // Map id->entid.
-        let mut temp_id_map: TempIdMap = TempIdMap::default();
+        // Map id->entids, collecting every entid each tempid upserts to.
+        let mut upserts: BTreeMap<TempId, BTreeSet<Entid>> = BTreeMap::new();
         for &(ref temp_id, ref av_pair) in temp_id_avs {
             if let Some(n) = av_map.get(&av_pair) {
-                if let Some(previous_n) = temp_id_map.get(&*temp_id) {
-                    if n != previous_n {
-                        // Conflicting upsert!  TODO: collect conflicts and give more details on what failed this transaction.
-                        bail!(ErrorKind::NotYetImplemented(format!("Conflicting upsert: tempid '{}' resolves to more than one entid: {:?}, {:?}", temp_id, previous_n, n))) // XXX
-                    }
-                }
-                temp_id_map.insert(temp_id.clone(), *n);
+                upserts.entry(temp_id.clone()).or_insert_with(BTreeSet::new).insert(*n);
             }
         }
 
+        // Map id->entid.
+        let mut temp_id_map: TempIdMap = TempIdMap::default();
+        for (temp_id, entids) in upserts {
+            if entids.len() > 1 {
+                let entids: Vec<String> = entids.into_iter().map(|e| self.describe_entid(e)).collect();
+                bail!(ErrorKind::ConflictingUpsert(temp_id.to_string(), entids))
+            }
+            temp_id_map.insert(temp_id, *entids.iter().next().unwrap());
+        }
+
         Ok((temp_id_map))
     }
 
+    /// Intern the lookup-ref [a v], ensuring that `a` is a :db/unique attribute and that `v` is in
+    /// the attribute's value set.
+    fn intern_lookup_ref(&self, lookup_refs: &mut intern_set::InternSet<AVPair>, a: &entmod::Entid, v: &edn::Value) -> Result<LookupRef> {
+        let a: i64 = match a {
+            &entmod::Entid::Entid(ref a) => *a,
+            &entmod::Entid::Ident(ref a) => self.schema.require_entid(&a)?,
+        };
+
+        let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
+        let typed_value: TypedValue = self.schema.to_typed_value(v, &attribute)?;
+
+        if !attribute.unique_value {
+            bail!(ErrorKind::LookupRefAttributeNotUnique(self.schema.require_ident(a)?.to_string(), typed_value))
+        }
+
+        Ok(lookup_refs.intern((a, typed_value)))
+    }
+
+    /// Resolve the given entid or ident to an entid.  The reserved ident `:db/tx` names the
+    /// transaction entity.
+    fn entid_for(&self, e: &entmod::Entid) -> Result<Entid> {
+        match e {
+            &entmod::Entid::Entid(ref e) => Ok(*e),
+            &entmod::Entid::Ident(ref e) if e.namespace == "db" && e.name == "tx" => Ok(self.tx_id),
+            &entmod::Entid::Ident(ref e) => self.schema.require_entid(&e),
+        }
+    }
+
+    /// Resolve an entity `e` (an entid or a lookup-ref) to an entid against the store as it was
+    /// before this transaction.  Transaction functions use this to inspect the current store.
+    fn resolve_entid_or_lookup_ref(&self, e: entmod::EntidOrLookupRef) -> Result<Entid> {
+        match e {
+            entmod::EntidOrLookupRef::Entid(e) => self.entid_for(&e),
+
+            entmod::EntidOrLookupRef::LookupRef(lookup_ref) => {
+                let mut lookup_refs = intern_set::InternSet::new();
+                let av = self.intern_lookup_ref(&mut lookup_refs, &lookup_ref.a, &lookup_ref.v)?;
+                let avs: Vec<&AVPair> = vec![&*av];
+                let av_map: AVMap = self.store.resolve_avs(&avs[..])?;
+                match av_map.values().next() {
+                    Some(e) => Ok(*e),
+                    None => bail!(ErrorKind::UnresolvedLookupRef(self.schema.require_ident(av.0)?.to_string(), av.1.clone())),
+                }
+            },
+        }
+    }
+
+    /// Convert an entity `e` (an entid, a lookup-ref, or a tempid) into a `Term` entity, interning
+    /// tempids and lookup-refs along the way.
+    fn entity_e_into_term_e(&self, e: entmod::EntidOrLookupRefOrTempId, temp_ids: &mut intern_set::InternSet<TempIdName>, lookup_refs: &mut intern_set::InternSet<AVPair>, internal_temp_ids: &mut i64, temp_id_partitions: &mut TempIdPartitionMap) -> Result<EntidOr<LookupRefOrTempId>> {
+        match e {
+            entmod::EntidOrLookupRefOrTempId::Entid(e) => {
+                Ok(std::result::Result::Ok(self.entid_for(&e)?))
+            },
+
+            entmod::EntidOrLookupRefOrTempId::TempId(ref e) if e == TX_TEMPID => {
+                Ok(std::result::Result::Ok(self.tx_id))
+            },
+
+            entmod::EntidOrLookupRefOrTempId::TempId(e) => {
+                Ok(std::result::Result::Err(LookupRefOrTempId::TempId(temp_ids.intern(TempIdName::External(e)))))
+            },
+
+            entmod::EntidOrLookupRefOrTempId::PartitionedTempId(temp_id) => {
+                Ok(std::result::Result::Err(LookupRefOrTempId::TempId(self.intern_partitioned_temp_id(temp_id, temp_ids, internal_temp_ids, temp_id_partitions)?)))
+            },
+
+            entmod::EntidOrLookupRefOrTempId::LookupRef(lookup_ref) => {
+                Ok(std::result::Result::Err(LookupRefOrTempId::LookupRef(self.intern_lookup_ref(lookup_refs, &lookup_ref.a, &lookup_ref.v)?)))
+            },
+        }
+    }
+
+    /// Intern the given tempid, recording the partition it is allocated from.  Anonymous tempids
+    /// are given a fresh internal tempid.
+    fn intern_partitioned_temp_id(&self, temp_id: entmod::PartitionedTempId, temp_ids: &mut intern_set::InternSet<TempIdName>, internal_temp_ids: &mut i64, temp_id_partitions: &mut TempIdPartitionMap) -> Result<TempId> {
+        let part = temp_id.part.to_string();
+        if !self.partition_map.contains_key(&part) {
+            bail!(ErrorKind::UnrecognizedPartition(part))
+        }
+
+        let temp_id = match temp_id.name {
+            Some(name) => temp_ids.intern(TempIdName::External(name)),
+            None => {
+                *internal_temp_ids += 1;
+                temp_ids.intern(TempIdName::Internal(*internal_temp_ids))
+            },
+        };
+
+        match temp_id_partitions.insert(temp_id.clone(), part.clone()) {
+            Some(other_part) if other_part != part => bail!(ErrorKind::ConflictingTempIdPartitions(temp_id.to_string(), other_part, part)),
+            _ => Ok(temp_id),
+        }
+    }
+
+    /// Convert a value `v` of the given `attribute` into a `Term` value, interning tempids and
+    /// lookup-refs along the way.
+    fn entity_v_into_term_v(&self, v: edn::Value, attribute: &Attribute, temp_ids: &mut intern_set::InternSet<TempIdName>, lookup_refs: &mut intern_set::InternSet<AVPair>, internal_temp_ids: &mut i64, temp_id_partitions: &mut TempIdPartitionMap) -> Result<TypedValueOr<LookupRefOrTempId>> {
+        if attribute.value_type == ValueType::Ref && (v.as_text().map_or(false, |t| t == TX_TEMPID) ||
+                                                      v.as_namespaced_keyword().map_or(false, |k| k.namespace == "db" && k.name == "tx")) {
+            Ok(std::result::Result::Ok(TypedValue::Ref(self.tx_id)))
+        } else if attribute.value_type == ValueType::Ref && v.is_text() {
+            Ok(std::result::Result::Err(LookupRefOrTempId::TempId(temp_ids.intern(TempIdName::External(v.as_text().unwrap().clone())))))
+        } else if attribute.value_type == ValueType::Ref && v.is_list() {
+            // A tempid (tempid :db.part/x "n") in value position.
+            match mentat_tx_parser::value_to_partitioned_temp_id(&v) {
+                Some(temp_id) => Ok(std::result::Result::Err(LookupRefOrTempId::TempId(self.intern_partitioned_temp_id(temp_id, temp_ids, internal_temp_ids, temp_id_partitions)?))),
+                None => bail!(ErrorKind::BadEDNValuePair(v.clone(), ValueType::Ref)),
+            }
+        } else if attribute.value_type == ValueType::Ref && v.is_vector() && v.as_vector().unwrap().len() == 2 {
+            // A lookup-ref [a v] in value position.  The parser doesn't know the
+            // attribute's value type, so it can't distinguish lookup-refs from
+            // other vectors; we do that here.
+            let lookup_ref = v.as_vector().unwrap();
+            let lookup_ref_a = match lookup_ref[0] {
+                edn::Value::Integer(a) => entmod::Entid::Entid(a),
+                edn::Value::NamespacedKeyword(ref a) => entmod::Entid::Ident(a.clone()),
+                _ => bail!(ErrorKind::BadEDNValuePair(v.clone(), ValueType::Ref)),
+            };
+            Ok(std::result::Result::Err(LookupRefOrTempId::LookupRef(self.intern_lookup_ref(lookup_refs, &lookup_ref_a, &lookup_ref[1])?)))
+        } else {
+            // Here is where we do schema-aware typechecking: we either assert that
+            // the given value is in the attribute's value set, or (in limited
+            // cases) coerce the value into the attribute's value set.
+            let typed_value: TypedValue = self.schema.to_typed_value(&v, &attribute)?;
+
+            Ok(std::result::Result::Ok(typed_value))
+        }
+    }
+
+    /// Convert a map notation value in entity position -- the value of `:db/id`, or a value of a
+    /// reversed attribute like `:person/_friends` -- into a `Term` entity.  Nested maps are
+    /// expanded into `terms`.
+    fn map_notation_value_into_term_e(&self, v: entmod::MapNotationValue, terms: &mut Vec<TermWithTempIdsAndLookupRefs>, temp_ids: &mut intern_set::InternSet<TempIdName>, lookup_refs: &mut intern_set::InternSet<AVPair>, internal_temp_ids: &mut i64, temp_id_partitions: &mut TempIdPartitionMap) -> Result<EntidOr<LookupRefOrTempId>> {
+        match v {
+            entmod::MapNotationValue::Atom(edn::Value::Integer(e)) => {
+                Ok(std::result::Result::Ok(e))
+            },
+
+            entmod::MapNotationValue::Atom(edn::Value::NamespacedKeyword(e)) => {
+                Ok(std::result::Result::Ok(self.entid_for(&entmod::Entid::Ident(e))?))
+            },
+
+            entmod::MapNotationValue::Atom(edn::Value::Text(e)) => {
+                self.entity_e_into_term_e(entmod::EntidOrLookupRefOrTempId::TempId(e), temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)
+            },
+
+            entmod::MapNotationValue::PartitionedTempId(temp_id) => {
+                self.entity_e_into_term_e(entmod::EntidOrLookupRefOrTempId::PartitionedTempId(temp_id), temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)
+            },
+
+            entmod::MapNotationValue::Vector(ref vs) if vs.len() == 2 => {
+                let a = match (&vs[0], &vs[1]) {
+                    (&entmod::MapNotationValue::Atom(edn::Value::Integer(a)), &entmod::MapNotationValue::Atom(_)) => entmod::Entid::Entid(a),
+                    (&entmod::MapNotationValue::Atom(edn::Value::NamespacedKeyword(ref a)), &entmod::MapNotationValue::Atom(_)) => entmod::Entid::Ident(a.clone()),
+                    _ => bail!(ErrorKind::BadMapNotation(format!("{:?} is not a lookup-ref", vs))),
+                };
+                let v = match vs[1] {
+                    entmod::MapNotationValue::Atom(ref v) => v,
+                    _ => unreachable!(),
+                };
+                Ok(std::result::Result::Err(LookupRefOrTempId::LookupRef(self.intern_lookup_ref(lookup_refs, &a, v)?)))
+            },
+
+            entmod::MapNotationValue::MapNotation(map) => {
+                self.map_notation_into_terms(map, terms, temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)
+            },
+
+            v => bail!(ErrorKind::BadMapNotation(format!("{:?} does not name an entity", v))),
+        }
+    }
+
+    /// Expand a map notation entity into `Term` instances, appending them to `terms`.
+    ///
+    /// Each map without a `:db/id` is given a fresh internal tempid.  Vectors under
+    /// :db.cardinality/many attributes are multiple values; nested maps are entities in their own
+    /// right, referenced by the enclosing entity; and reversed attributes like `:person/_friends`
+    /// make the enclosing entity the value rather than the entity of each assertion.
+    ///
+    /// Returns the entity of the given map.
+    fn map_notation_into_terms(&self, mut map: entmod::MapNotation, terms: &mut Vec<TermWithTempIdsAndLookupRefs>, temp_ids: &mut intern_set::InternSet<TempIdName>, lookup_refs: &mut intern_set::InternSet<AVPair>, internal_temp_ids: &mut i64, temp_id_partitions: &mut TempIdPartitionMap) -> Result<EntidOr<LookupRefOrTempId>> {
+        let db_id = entmod::Entid::Ident(NamespacedKeyword::new("db", "id"));
+
+        let e: EntidOr<LookupRefOrTempId> = match map.remove(&db_id) {
+            Some(entmod::MapNotationValue::MapNotation(_)) => {
+                bail!(ErrorKind::BadMapNotation(":db/id cannot be a map".to_string()))
+            },
+            Some(v) => self.map_notation_value_into_term_e(v, terms, temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)?,
+            None => {
+                *internal_temp_ids += 1;
+                std::result::Result::Err(LookupRefOrTempId::TempId(temp_ids.intern(TempIdName::Internal(*internal_temp_ids))))
+            },
+        };
+
+        for (a, v) in map {
+            let reversed = match a {
+                entmod::Entid::Ident(ref a) => a.is_backward(),
+                entmod::Entid::Entid(_) => false,
+            };
+
+            let a: Entid = match a {
+                entmod::Entid::Ident(ref a) if reversed => self.schema.require_entid(&a.to_reversed())?,
+                ref a => self.entid_for(a)?,
+            };
+
+            let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
+
+            if reversed && attribute.value_type != ValueType::Ref {
+                bail!(ErrorKind::BadMapNotation(format!("reversed attribute {} is not :db.type/ref", self.schema.require_ident(a)?)))
+            }
+
+            let vs: Vec<entmod::MapNotationValue> = match v {
+                entmod::MapNotationValue::Vector(vs) if attribute.multival => vs,
+                v => vec![v],
+            };
+
+            for v in vs {
+                if reversed {
+                    let reversed_e = self.map_notation_value_into_term_e(v, terms, temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)?;
+                    let reversed_v = e.clone().map(TypedValue::Ref);
+                    terms.push(Term::AddOrRetract(OpType::Add, reversed_e, a, reversed_v));
+                    continue;
+                }
+
+                let v: TypedValueOr<LookupRefOrTempId> = match v {
+                    entmod::MapNotationValue::Atom(v) => {
+                        self.entity_v_into_term_v(v, attribute, temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)?
+                    },
+
+                    entmod::MapNotationValue::Vector(vs) => {
+                        // A lookup-ref, or a vector value of a :db.cardinality/one attribute.
+                        let vs: Vec<edn::Value> = vs.into_iter().map(|v| -> Result<edn::Value> { match v {
+                            entmod::MapNotationValue::Atom(v) => Ok(v),
+                            v => bail!(ErrorKind::BadMapNotation(format!("{:?} cannot be nested in a vector", v))),
+                        }}).collect::<Result<Vec<_>>>()?;
+                        self.entity_v_into_term_v(edn::Value::Vector(vs), attribute, temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)?
+                    },
+
+                    entmod::MapNotationValue::MapNotation(map) => {
+                        if attribute.value_type != ValueType::Ref {
+                            bail!(ErrorKind::BadMapNotation(format!("nested map under attribute {} that is not :db.type/ref", self.schema.require_ident(a)?)))
+                        }
+                        self.map_notation_into_terms(map, terms, temp_ids, lookup_refs, internal_temp_ids, temp_id_partitions)?.map(TypedValue::Ref)
+                    },
+
+                    entmod::MapNotationValue::PartitionedTempId(temp_id) => {
+                        if attribute.value_type != ValueType::Ref {
+                            bail!(ErrorKind::BadMapNotation(format!("tempid under attribute {} that is not :db.type/ref", self.schema.require_ident(a)?)))
+                        }
+                        std::result::Result::Err(LookupRefOrTempId::TempId(self.intern_partitioned_temp_id(temp_id, temp_ids, internal_temp_ids, temp_id_partitions)?))
+                    },
+                };
+
+                terms.push(Term::AddOrRetract(OpType::Add, e.clone(), a, v));
+            }
+        }
+
+        Ok(e)
+    }
+
     /// Pipeline stage 1: convert `Entity` instances into `Term` instances, ready for term
     /// rewriting.
     ///
-    /// The `Term` instances produce share interned TempId and LookupRef handles.
-    fn entities_into_terms_with_temp_ids_and_lookup_refs<I>(&self, entities: I) -> Result<Vec<TermWithTempIdsAndLookupRefs>> where I: IntoIterator<Item=Entity> {
+    /// The `Term` instances produce share interned TempId and LookupRef handles.  The given
+    /// `lookup_refs` collects the lookup-refs that need to be resolved in Pipeline stage 2, and the
+    /// given `temp_id_partitions` collects the partitions that tempids name.
+    fn entities_into_terms_with_temp_ids_and_lookup_refs<I>(&self, entities: I, lookup_refs: &mut intern_set::InternSet<AVPair>, temp_id_partitions: &mut TempIdPartitionMap) -> Result<Vec<TermWithTempIdsAndLookupRefs>> where I: IntoIterator<Item=Entity> {
         let mut temp_ids = intern_set::InternSet::new();
+        let mut internal_temp_ids: i64 = 0;
+        let mut terms: Vec<TermWithTempIdsAndLookupRefs> = vec![];
 
-        entities.into_iter()
-            .map(|entity: Entity| -> Result<TermWithTempIdsAndLookupRefs> {
-                match entity {
-                    Entity::AddOrRetract { op, e, a, v } => {
-                        let a: i64 = match a {
-                            entmod::Entid::Entid(ref a) => *a,
-                            entmod::Entid::Ident(ref a) => self.schema.require_entid(&a)?,
-                        };
+        for entity in entities {
+            match entity {
+                Entity::AddOrRetract { op, e, a, v } => {
+                    let a: i64 = self.entid_for(&a)?;
+                    let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
 
-                        let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
-
-                        let e = match e {
-                            entmod::EntidOrLookupRefOrTempId::Entid(e) => {
-                                let e: i64 = match e {
-                                    entmod::Entid::Entid(ref e) => *e,
-                                    entmod::Entid::Ident(ref e) => self.schema.require_entid(&e)?,
-                                };
-                                std::result::Result::Ok(e)
-                            },
-
-                            entmod::EntidOrLookupRefOrTempId::TempId(e) => {
-                                std::result::Result::Err(LookupRefOrTempId::TempId(temp_ids.intern(e)))
-                            },
-
-                            entmod::EntidOrLookupRefOrTempId::LookupRef(_) => {
-                                // TODO: reference entity and initial input.
-                                bail!(ErrorKind::NotYetImplemented(format!("Transacting lookup-refs is not yet implemented")))
-                            },
-                        };
+                    let e = self.entity_e_into_term_e(e, &mut temp_ids, lookup_refs, &mut internal_temp_ids, temp_id_partitions)?;
+                    let v = self.entity_v_into_term_v(v, attribute, &mut temp_ids, lookup_refs, &mut internal_temp_ids, temp_id_partitions)?;
 
-                        let v = {
-                            if attribute.value_type == ValueType::Ref && v.is_text() {
-                                std::result::Result::Err(LookupRefOrTempId::TempId(temp_ids.intern(v.as_text().unwrap().clone())))
-                            } else if attribute.value_type == ValueType::Ref && v.is_vector() && v.as_vector().unwrap().len() == 2 {
-                                bail!(ErrorKind::NotYetImplemented(format!("Transacting lookup-refs is not yet implemented")))
-                            } else {
-                                // Here is where we do schema-aware typechecking: we either assert that
-                                // the given value is in the attribute's value set, or (in limited
-                                // cases) coerce the value into the attribute's value set.
-                                let typed_value: TypedValue = self.schema.to_typed_value(&v, &attribute)?;
-
-                                std::result::Result::Ok(typed_value)
-                            }
-                        };
+                    terms.push(Term::AddOrRetract(op, e, a, v));
+                },
 
-                        Ok(Term::AddOrRetract(op, e, a, v))
-                    },
-                }
-            })
-            .collect::<Result<Vec<_>>>()
+                Entity::MapNotation(map) => {
+                    // Nothing refers to a top-level map's entity.
+                    let _ = self.map_notation_into_terms(map, &mut terms, &mut temp_ids, lookup_refs, &mut internal_temp_ids, temp_id_partitions)?;
+                },
+
+                Entity::RetractEntity { e } => {
+                    let e: Entid = self.resolve_entid_or_lookup_ref(e)?;
+
+                    let component_attributes: Vec<Entid> = self.schema.schema_map.iter()
+                        .filter(|&(_, attribute)| attribute.component)
+                        .map(|(a, _)| *a)
+                        .collect();
+
+                    for (e, a, v) in db::read_retract_entity_datoms(self.store, e, &component_attributes[..])? {
+                        terms.push(Term::AddOrRetract(OpType::Retract, std::result::Result::Ok(e), a, std::result::Result::Ok(v)));
+                    }
+                },
+
+                Entity::Cas { e, a, old, new } => {
+                    let e: Entid = self.resolve_entid_or_lookup_ref(e)?;
+                    let a: Entid = self.entid_for(&a)?;
+                    let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
+
+                    if attribute.multival {
+                        bail!(ErrorKind::NotYetImplemented(format!(":db.fn/cas on :db.cardinality/many attribute {}", self.schema.require_ident(a)?)))
+                    }
+
+                    let expected: Option<TypedValue> = match old {
+                        edn::Value::Nil => None,
+                        old => Some(self.schema.to_typed_value(&old, &attribute)?),
+                    };
+
+                    // The store hasn't been written yet, so this is the value before this transaction.
+                    let actual: Option<TypedValue> = db::read_values(self.store, e, a)?.into_iter().next();
+
+                    if expected != actual {
+                        bail!(ErrorKind::CasFailed(e, self.schema.require_ident(a)?.to_string(), expected, actual))
+                    }
+
+                    let v = self.entity_v_into_term_v(new, attribute, &mut temp_ids, lookup_refs, &mut internal_temp_ids, temp_id_partitions)?;
+                    terms.push(Term::AddOrRetract(OpType::Add, std::result::Result::Ok(e), a, v));
+                },
+            }
+        }
+
+        Ok(terms)
     }
 
     /// Pipeline stage 2: rewrite `Term` instances with lookup refs into `Term` instances without
@@ -228,8 +550,8 @@ impl<'conn, 'a> Tx<'conn, 'a> {
         terms.into_iter().map(|term: TermWithTempIdsAndLookupRefs| -> Result<TermWithTempIds> {
             match term {
                 Term::AddOrRetract(op, e, a, v) => {
-                    let e = replace_lookup_ref(&lookup_ref_map, e, |x| x)?;
-                    let v = replace_lookup_ref(&lookup_ref_map, v, |x| TypedValue::Ref(x))?;
+                    let e = replace_lookup_ref(&self.schema, &lookup_ref_map, e, |x| x)?;
+                    let v = replace_lookup_ref(&self.schema, &lookup_ref_map, v, |x| TypedValue::Ref(x))?;
                     Ok(Term::AddOrRetract(op, e, a, v))
                 },
             }
@@ -243,12 +565,12 @@ impl<'conn, 'a> Tx<'conn, 'a> {
     pub fn transact_entities<I>(&mut self, entities: I) -> Result<TxReport> where I: IntoIterator<Item=Entity> {
         // TODO: push these into an internal transaction report?
 
-        // We don't yet support lookup refs, so this isn't mutable.  Later, it'll be mutable.
-        let lookup_refs: intern_set::InternSet<AVPair> = intern_set::InternSet::new();
+        let mut lookup_refs: intern_set::InternSet<AVPair> = intern_set::InternSet::new();
+        let mut temp_id_partitions: TempIdPartitionMap = TempIdPartitionMap::default();
 
         // TODO: extract the tempids set as well.
         // Pipeline stage 1: entities -> terms with tempids and lookup refs.
-        let terms_with_temp_ids_and_lookup_refs = self.entities_into_terms_with_temp_ids_and_lookup_refs(entities)?;
+        let terms_with_temp_ids_and_lookup_refs = self.entities_into_terms_with_temp_ids_and_lookup_refs(entities, &mut lookup_refs, &mut temp_id_partitions)?;
 
         // Pipeline stage 2: resolve lookup refs -> terms with tempids.
         let lookup_ref_avs: Vec<&(i64, TypedValue)> = lookup_refs.inner.iter().map(|rc| &**rc).collect();
@@ -260,20 +582,32 @@ impl<'conn, 'a> Tx<'conn, 'a> {
         // Now we can collect upsert populations.
         let (mut generation, inert_terms) = Generation::from(terms_with_temp_ids, &self.schema)?;
 
+        // Tempids named in the transaction, mapped to the entids they resolve to.
+        let mut tempids: BTreeMap<String, Entid> = BTreeMap::new();
+
         // And evolve them forward.
         while generation.can_evolve() {
             // Evolve further.
             let temp_id_map = self.resolve_temp_id_avs(&generation.temp_id_avs()[..])?;
+            add_external_temp_ids(&mut tempids, &temp_id_map);
             generation = generation.evolve_one_step(&temp_id_map);
         }
 
         // Allocate entids for tempids that didn't upsert.  BTreeSet rather than HashSet so this is deterministic.
         let unresolved_temp_ids: BTreeSet<TempId> = generation.temp_ids_in_allocations();
 
-        // TODO: track partitions for temporary IDs.
-        let entids = self.partition_map.allocate_entids(":db.part/user", unresolved_temp_ids.len());
-
-        let temp_id_allocations: TempIdMap = unresolved_temp_ids.into_iter().zip(entids).collect();
+        // Each tempid is allocated from the partition it names, or from :db.part/user.
+        let mut temp_id_allocations: TempIdMap = TempIdMap::default();
+        for temp_id in unresolved_temp_ids {
+            let part = temp_id_partitions.get(&temp_id).map(|part| part.as_str()).unwrap_or(":db.part/user");
+            let entid = self.partition_map.allocate_entid(part);
+            // The end of :db.part/db is reserved for bootstrap idents.
+            if part == ":db.part/db" && entid >= entids::BOOTSTRAP_RESERVED_START {
+                bail!(ErrorKind::PartitionExhausted(part.to_string()));
+            }
+            temp_id_allocations.insert(temp_id, entid);
+        }
+        add_external_temp_ids(&mut tempids, &temp_id_allocations);
 
         let final_populations = generation.into_final_populations(&temp_id_allocations)?;
 
@@ -283,39 +617,94 @@ impl<'conn, 'a> Tx<'conn, 'a> {
         /// Assertions that are :db.cardinality/many and not :db.fulltext.
         let mut non_fts_many: Vec<db::ReducedEntity> = vec![];
 
+        /// Assertions that are :db.cardinality/one and :db.fulltext.
+        let mut fts_one: Vec<db::ReducedEntity> = vec![];
+
+        /// Assertions that are :db.cardinality/many and :db.fulltext.
+        let mut fts_many: Vec<db::ReducedEntity> = vec![];
+
+        // Retracting fulltext values that aren't present leaves unreferenced fulltext values.
+        let mut fts_retracted = false;
+
+        // The :db/txInstant asserted for the transaction entity, if any.
+        let mut tx_instant: Option<DateTime<Utc>> = None;
+
+        // The value asserted for each [e a] of a :db.cardinality/one attribute.
+        let mut asserted_one: HashMap<(Entid, Entid), TypedValue> = HashMap::new();
+
         let final_terms: Vec<TermWithoutTempIds> = [final_populations.resolved,
                                                     final_populations.allocated,
                                                     inert_terms.into_iter().map(|term| term.unwrap()).collect()].concat();
 
         // Pipeline stage 4: final terms (after rewriting) -> DB insertions.
-        // Collect into non_fts_*.
+        // Collect into non_fts_* and fts_*.
         // TODO: use something like Clojure's group_by to do this.
         for term in final_terms {
             match term {
                 Term::AddOrRetract(op, e, a, v) => {
-                    let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
-                    if attribute.fulltext {
-                        bail!(ErrorKind::NotYetImplemented(format!("Transacting :db/fulltext entities is not yet implemented"))) // TODO: reference original input.  Difficult!
+                    // Attributes are altered by asserting their new properties directly; don't
+                    // silently accept Datomic's explicit alteration marker.
+                    if a == entids::DB_ALTER_ATTRIBUTE {
+                        let ident = match v {
+                            TypedValue::Ref(entid) => self.schema.get_ident(entid).map_or_else(|| entid.to_string(), |ident| ident.to_string()),
+                            ref v => format!("{:?}", v),
+                        };
+                        bail!(ErrorKind::SchemaAlterationFailed(ident, ":db.alter/attribute is not supported; assert the altered properties directly".to_string()));
                     }
 
+                    let attribute: &Attribute = self.schema.require_attribute_for_entid(a)?;
+
                     let added = op == OpType::Add;
-                    if attribute.multival {
-                        non_fts_many.push((e, a, attribute, v, added));
-                    } else {
-                        non_fts_one.push((e, a, attribute, v, added));
+
+                    if a == entids::DB_TX_INSTANT {
+                        if e != self.tx_id || !added {
+                            bail!(ErrorKind::BadTxInstant("only the transaction entity's :db/txInstant can be asserted".to_string()))
+                        }
+                        if let TypedValue::Instant(instant) = v {
+                            let previous = db::read_last_tx_instant(self.store)?;
+                            if previous.map_or(false, |previous| instant < previous) {
+                                bail!(ErrorKind::BadTxInstant(format!("{} is before the previous transaction's :db/txInstant {}", instant, previous.unwrap())))
+                            }
+                            tx_instant = Some(instant);
+                        }
                     }
+
+                    if added && !attribute.multival {
+                        if let Some(existing) = asserted_one.insert((e, a), v.clone()) {
+                            if existing != v {
+                                bail!(ErrorKind::CardinalityConflict(self.describe_entid(e), self.describe_entid(a), vec![existing, v]))
+                            }
+                        }
+                    }
+
+                    match (attribute.fulltext, attribute.multival) {
+                        (false, true) => non_fts_many.push((e, a, attribute, v, added)),
+                        (false, false) => non_fts_one.push((e, a, attribute, v, added)),
+                        (true, true) => fts_many.push((e, a, attribute, v, added)),
+                        (true, false) => fts_one.push((e, a, attribute, v, added)),
+                    }
+                    fts_retracted |= attribute.fulltext && !added;
                 },
             }
         }
 
-        // Transact [:db/add :db/txInstant NOW :db/tx].
-        // TODO: allow this to be present in the transaction data.
-        non_fts_one.push((self.tx_id,
-                          entids::DB_TX_INSTANT,
-                          // TODO: extract this to a constant.
-                          self.schema.require_attribute_for_entid(self.schema.require_entid(&to_namespaced_keyword(":db/txInstant").unwrap())?)?,
-                          TypedValue::Long(self.tx_instant),
-                          true));
+        // Transact [:db/add :db/txInstant NOW :db/tx], unless the transaction data supplies its own
+        // :db/txInstant.
+        match tx_instant {
+            Some(instant) => self.tx_instant = instant,
+            None => {
+                // The system clock can go backwards, but transaction instants must not.
+                if let Some(previous) = db::read_last_tx_instant(self.store)? {
+                    self.tx_instant = std::cmp::max(self.tx_instant, previous);
+                }
+                non_fts_one.push((self.tx_id,
+                                  entids::DB_TX_INSTANT,
+                                  // TODO: extract this to a constant.
+                                  self.schema.require_attribute_for_entid(self.schema.require_entid(&to_namespaced_keyword(":db/txInstant").unwrap())?)?,
+                                  TypedValue::Instant(self.tx_instant),
+                                  true));
+            },
+        }
 
         if !non_fts_one.is_empty() {
             self.store.insert_non_fts_searches(&non_fts_one[..], db::SearchType::Inexact)?;
@@ -325,18 +714,88 @@ impl<'conn, 'a> Tx<'conn, 'a> {
             self.store.insert_non_fts_searches(&non_fts_many[..], db::SearchType::Exact)?;
         }
 
+        if !fts_one.is_empty() {
+            self.store.insert_fts_searches(&fts_one[..], db::SearchType::Inexact)?;
+        }
+
+        if !fts_many.is_empty() {
+            self.store.insert_fts_searches(&fts_many[..], db::SearchType::Exact)?;
+        }
+
+        if let Some((a, v, existing_e)) = db::find_unique_value_violation(self.store)? {
+            bail!(ErrorKind::UniqueValueViolation(self.describe_entid(a), v, self.describe_entid(existing_e)))
+        }
+
         self.store.commit_transaction(self.tx_id)?;
 
-        // TODO: update idents and schema materialized views.
+        if fts_retracted {
+            let rowids = db::unmatched_fulltext_retractions(self.store)?;
+            db::garbage_collect_fulltext_values(self.store, &rowids)?;
+        }
+
+        let tx_data = db::read_tx_data(self.store, self.tx_id)?;
+
+        // Excise the entities named by [request :db/excise e] assertions.  The request itself is
+        // recorded by this transaction.
+        let mut excised: BTreeSet<Entid> = BTreeSet::new();
+        for datom in tx_data.iter().filter(|datom| datom.a == entids::DB_EXCISE && datom.added) {
+            let e = match datom.v {
+                TypedValue::Ref(e) => e,
+                _ => unreachable!(),
+            };
+            self.excise(&tx_data[..], datom.e, e)?;
+            excised.insert(e);
+        }
+
+        // If the transaction changed idents or schema, update the materialized views and produce
+        // the next schema.  Reading the schema validates it, and altering attributes validates the
+        // existing data; either failure fails the transaction.  Excision can remove idents.
+        let metadata_entities: BTreeSet<Entid> = tx_data.iter()
+            .filter(|datom| entids::might_update_metadata(datom.a))
+            .map(|datom| datom.e)
+            .chain(excised)
+            .collect();
+        if !metadata_entities.is_empty() {
+            db::update_idents_and_schema(self.store, &metadata_entities)?;
+            let ident_map = db::read_ident_map(self.store)?;
+            let next_schema = db::read_schema(self.store, &ident_map)?;
+            db::alter_attributes(self.store, &self.schema, &next_schema)?;
+            self.schema = Cow::Owned(next_schema);
+        }
+
+        // Install the partitions named by [... :db.install/partition e] assertions.  The partition
+        // is named by the ident of `e`; installing an existing partition does nothing.
+        for datom in tx_data.iter().filter(|datom| datom.a == entids::DB_INSTALL_PARTITION && datom.added) {
+            let part = match datom.v {
+                TypedValue::Ref(e) => self.schema.require_ident(e)?.to_string(),
+                _ => unreachable!(),
+            };
+            if !self.partition_map.contains_key(&part) {
+                db::install_partition(self.store, &mut self.partition_map, part)?;
+            }
+        }
+
         db::update_partition_map(self.store, &self.partition_map)?;
 
         Ok(TxReport {
             tx_id: self.tx_id,
             tx_instant: self.tx_instant,
+            tempids: tempids,
+            tx_data: tx_data,
         })
     }
 }
 
+/// Add the tempids named in the transaction, and the entids they resolved to, to `tempids`.
+/// Internal tempids, allocated while expanding map notation, are not named and are skipped.
+fn add_external_temp_ids(tempids: &mut BTreeMap<String, Entid>, temp_id_map: &TempIdMap) {
+    for (temp_id, entid) in temp_id_map {
+        if let TempIdName::External(ref name) = **temp_id {
+            tempids.insert(name.clone(), *entid);
+        }
+    }
+}
+
 /// Transact the given `entities` against the given SQLite `conn`, using the given metadata.
 ///
 /// This approach is explained in https://github.com/mozilla/mentat/wiki/Transacting.
@@ -15,6 +15,9 @@ use std::collections::BTreeMap;
 
 extern crate mentat_core;
 
+use chrono::{DateTime, Utc};
+use edn::symbols;
+
 pub use self::mentat_core::{
     Entid,
     ValueType,
@@ -76,16 +79,47 @@ pub type AVPair = (Entid, TypedValue);
 /// Used to resolve lookup-refs and upserts.
 pub type AVMap<'a> = HashMap<&'a AVPair, Entid>;
 
+/// A datom [e a v tx added] asserted (`added` is true) or retracted (`added` is false) by a
+/// transaction.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+pub struct Datom {
+    pub e: Entid,
+    pub a: Entid,
+    pub v: TypedValue,
+    pub tx: Entid,
+    pub added: bool,
+}
+
 /// A transaction report summarizes an applied transaction.
-// TODO: include map of resolved tempids.
-#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialOrd, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
 pub struct TxReport {
     /// The transaction ID of the transaction.
     pub tx_id: Entid,
 
-    /// The timestamp when the transaction began to be committed.
-    ///
-    /// This is milliseconds after the Unix epoch according to the transactor's local clock.
-    // TODO: :db.type/instant.
-    pub tx_instant: i64,
+    /// The timestamp when the transaction began to be committed, according to the transactor's
+    /// local clock.
+    pub tx_instant: DateTime<Utc>,
+
+    /// A map from string tempids to the entids they resolved to, either by upserting or by
+    /// allocating a new entid.
+    pub tempids: BTreeMap<String, Entid>,
+
+    /// The datoms that the transaction actually asserted or retracted, including the transaction's
+    /// :db/txInstant.  Assertions of datoms already present, and retractions of datoms not
+    /// present, are not included.
+    pub tx_data: Vec<Datom>,
+}
+
+/// A transaction read back from the transaction log.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+pub struct TxLogEntry {
+    /// The transaction ID of the transaction.
+    pub tx_id: Entid,
+
+    /// The transaction's :db/txInstant.
+    pub tx_instant: DateTime<Utc>,
+
+    /// The datoms [e a v added] that the transaction asserted or retracted, including its
+    /// :db/txInstant, with attributes named by their idents.
+    pub tx_data: Vec<(Entid, symbols::NamespacedKeyword, TypedValue, bool)>,
 }
@@ -8,10 +8,13 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+extern crate base64;
+extern crate chrono;
 extern crate itertools;
 extern crate num;
 extern crate ordered_float;
 extern crate pretty;
+extern crate uuid;
 
 pub mod symbols;
 pub mod types;
@@ -23,8 +26,10 @@ pub mod parse {
     include!(concat!(env!("OUT_DIR"), "/edn.rs"));
 }
 
+pub use chrono::{DateTime, Utc};
 pub use num::BigInt;
 pub use ordered_float::OrderedFloat;
 pub use parse::ParseError;
 pub use types::Value;
+pub use uuid::Uuid;
 pub use symbols::{Keyword, NamespacedKeyword, PlainSymbol, NamespacedSymbol};
@@ -15,9 +15,12 @@ use std::cmp::{Ordering, Ord, PartialOrd};
 use std::fmt::{Display, Formatter};
 use std::f64;
 
+use base64;
+use chrono::{DateTime, SecondsFormat, Utc};
 use symbols;
 use num::BigInt;
 use ordered_float::OrderedFloat;
+use uuid::Uuid;
 
 /// Value represents one of the allowed values in an EDN string.
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
@@ -27,6 +30,9 @@ pub enum Value {
     Integer(i64),
     BigInteger(BigInt),
     Float(OrderedFloat<f64>),
+    Instant(DateTime<Utc>),
+    Uuid(Uuid),
+    Bytes(Vec<u8>),
     Text(String),
     PlainSymbol(symbols::PlainSymbol),
     NamespacedSymbol(symbols::NamespacedSymbol),
@@ -54,6 +60,9 @@ pub enum SpannedValue {
     Integer(i64),
     BigInteger(BigInt),
     Float(OrderedFloat<f64>),
+    Instant(DateTime<Utc>),
+    Uuid(Uuid),
+    Bytes(Vec<u8>),
     Text(String),
     PlainSymbol(symbols::PlainSymbol),
     NamespacedSymbol(symbols::NamespacedSymbol),
@@ -85,6 +94,9 @@ impl From<SpannedValue> for Value {
             SpannedValue::Integer(v) => Value::Integer(v),
             SpannedValue::BigInteger(v) => Value::BigInteger(v),
             SpannedValue::Float(v) => Value::Float(v),
+            SpannedValue::Instant(v) => Value::Instant(v),
+            SpannedValue::Uuid(v) => Value::Uuid(v),
+            SpannedValue::Bytes(v) => Value::Bytes(v),
             SpannedValue::Text(v) => Value::Text(v),
             SpannedValue::PlainSymbol(v) => Value::PlainSymbol(v),
             SpannedValue::NamespacedSymbol(v) => Value::NamespacedSymbol(v),
@@ -230,6 +242,9 @@ macro_rules! def_common_value_methods {
         def_is!(is_integer, $t::Integer(_));
         def_is!(is_big_integer, $t::BigInteger(_));
         def_is!(is_float, $t::Float(_));
+        def_is!(is_instant, $t::Instant(_));
+        def_is!(is_uuid, $t::Uuid(_));
+        def_is!(is_bytes, $t::Bytes(_));
         def_is!(is_text, $t::Text(_));
         def_is!(is_symbol, $t::PlainSymbol(_));
         def_is!(is_namespaced_symbol, $t::NamespacedSymbol(_));
@@ -249,9 +264,12 @@ macro_rules! def_common_value_methods {
         def_as!(as_boolean, $t::Boolean, bool,);
         def_as!(as_integer, $t::Integer, i64,);
         def_as!(as_float, $t::Float, f64, |v: OrderedFloat<f64>| v.into_inner());
+        def_as!(as_instant, $t::Instant, DateTime<Utc>,);
+        def_as!(as_uuid, $t::Uuid, Uuid,);
 
         def_as_ref!(as_big_integer, $t::BigInteger, BigInt);
         def_as_ref!(as_ordered_float, $t::Float, OrderedFloat<f64>);
+        def_as_ref!(as_bytes, $t::Bytes, Vec<u8>);
         def_as_ref!(as_text, $t::Text, String);
         def_as_ref!(as_symbol, $t::PlainSymbol, symbols::PlainSymbol);
         def_as_ref!(as_namespaced_symbol, $t::NamespacedSymbol, symbols::NamespacedSymbol);
@@ -267,6 +285,9 @@ macro_rules! def_common_value_methods {
         def_into!(into_big_integer, $t::BigInteger, BigInt,);
         def_into!(into_ordered_float, $t::Float, OrderedFloat<f64>,);
         def_into!(into_float, $t::Float, f64, |v: OrderedFloat<f64>| v.into_inner());
+        def_into!(into_instant, $t::Instant, DateTime<Utc>,);
+        def_into!(into_uuid, $t::Uuid, Uuid,);
+        def_into!(into_bytes, $t::Bytes, Vec<u8>,);
         def_into!(into_text, $t::Text, String,);
         def_into!(into_symbol, $t::PlainSymbol, symbols::PlainSymbol,);
         def_into!(into_namespaced_symbol, $t::NamespacedSymbol, symbols::NamespacedSymbol,);
@@ -296,15 +317,18 @@ macro_rules! def_common_value_methods {
                 $t::Integer(_) => 2,
                 $t::BigInteger(_) => 3,
                 $t::Float(_) => 4,
-                $t::Text(_) => 5,
-                $t::PlainSymbol(_) => 6,
-                $t::NamespacedSymbol(_) => 7,
-                $t::Keyword(_) => 8,
-                $t::NamespacedKeyword(_) => 9,
-                $t::Vector(_) => 10,
-                $t::List(_) => 11,
-                $t::Set(_) => 12,
-                $t::Map(_) => 13,
+                $t::Instant(_) => 5,
+                $t::Uuid(_) => 6,
+                $t::Bytes(_) => 7,
+                $t::Text(_) => 8,
+                $t::PlainSymbol(_) => 9,
+                $t::NamespacedSymbol(_) => 10,
+                $t::Keyword(_) => 11,
+                $t::NamespacedKeyword(_) => 12,
+                $t::Vector(_) => 13,
+                $t::List(_) => 14,
+                $t::Set(_) => 15,
+                $t::Map(_) => 16,
             }
         }
     }
@@ -320,6 +344,9 @@ macro_rules! def_common_value_ord {
             (&$t::Integer(a), &$t::Integer(b)) => b.cmp(&a),
             (&$t::BigInteger(ref a), &$t::BigInteger(ref b)) => b.cmp(a),
             (&$t::Float(ref a), &$t::Float(ref b)) => b.cmp(a),
+            (&$t::Instant(ref a), &$t::Instant(ref b)) => b.cmp(a),
+            (&$t::Uuid(ref a), &$t::Uuid(ref b)) => b.cmp(a),
+            (&$t::Bytes(ref a), &$t::Bytes(ref b)) => b.cmp(a),
             (&$t::Text(ref a), &$t::Text(ref b)) => b.cmp(a),
             (&$t::PlainSymbol(ref a), &$t::PlainSymbol(ref b)) => b.cmp(a),
             (&$t::NamespacedSymbol(ref a), &$t::NamespacedSymbol(ref b)) => b.cmp(a),
@@ -356,6 +383,9 @@ macro_rules! def_common_value_display {
                     write!($f, "{}", v)
                 }
             }
+            $t::Instant(ref v) => write!($f, "#inst \"{}\"", v.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            $t::Uuid(ref v) => write!($f, "#uuid \"{}\"", v.hyphenated()),
+            $t::Bytes(ref v) => write!($f, "#bytes \"{}\"", base64::encode(v)),
             // TODO: EDN escaping.
             $t::Text(ref v) => write!($f, "{}", v),
             $t::PlainSymbol(ref v) => v.fmt($f),
@@ -8,17 +8,21 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+extern crate chrono;
 extern crate edn;
 extern crate num;
 extern crate ordered_float;
+extern crate uuid;
 
 use std::collections::{BTreeSet, BTreeMap, LinkedList};
 use std::iter::FromIterator;
 use std::f64;
 
+use chrono::{TimeZone, Utc};
 use num::bigint::ToBigInt;
 use num::traits::{Zero, One};
 use ordered_float::OrderedFloat;
+use uuid::Uuid;
 
 use edn::parse::{self, ParseError};
 use edn::types::{Value, SpannedValue, Span, ValueAndSpan};
@@ -67,6 +71,9 @@ fn_parse_into_value!(hexinteger);
 fn_parse_into_value!(basedinteger);
 fn_parse_into_value!(integer);
 fn_parse_into_value!(float);
+fn_parse_into_value!(inst);
+fn_parse_into_value!(uuid);
+fn_parse_into_value!(bytes);
 fn_parse_into_value!(text);
 fn_parse_into_value!(symbol);
 fn_parse_into_value!(keyword);
@@ -290,6 +297,55 @@ fn test_span_float() {
     });
 }
 
+#[test]
+fn test_inst() {
+    use self::Value::*;
+
+    let expected = Utc.ymd(2017, 4, 28).and_hms_milli(20, 23, 5, 187);
+    assert_eq!(inst("#inst \"2017-04-28T20:23:05.187Z\"").unwrap(), Instant(expected));
+    assert_eq!(inst("#inst \"2017-04-28T13:23:05.187-07:00\"").unwrap(), Instant(expected));
+    assert_eq!(inst("#inst \"1970-01-01T00:00:00Z\"").unwrap(), Instant(Utc.timestamp(0, 0)));
+
+    // Instants print as they parse.
+    assert_eq!(Instant(expected).to_string(), "#inst \"2017-04-28T20:23:05.187Z\"");
+    assert_eq!(value(Instant(expected).to_string().as_str()).unwrap(), Instant(expected));
+
+    assert!(inst("#inst \"2017-04-28\"").is_err());
+    assert!(inst("#inst \"2017-13-28T20:23:05Z\"").is_err());
+    assert!(inst("\"2017-04-28T20:23:05Z\"").is_err());
+}
+
+#[test]
+fn test_uuid() {
+    let expected = Uuid::parse_str("f47ac10b-58cc-4372-a567-0e02b2c3d479").unwrap();
+    assert_eq!(uuid("#uuid \"f47ac10b-58cc-4372-a567-0e02b2c3d479\"").unwrap(), Value::Uuid(expected));
+    assert_eq!(uuid("#uuid \"F47AC10B-58CC-4372-A567-0E02B2C3D479\"").unwrap(), Value::Uuid(expected));
+
+    // UUIDs print in lowercase, hyphenated form.
+    assert_eq!(Value::Uuid(expected).to_string(), "#uuid \"f47ac10b-58cc-4372-a567-0e02b2c3d479\"");
+    assert_eq!(value(Value::Uuid(expected).to_string().as_str()).unwrap(), Value::Uuid(expected));
+
+    assert!(uuid("#uuid \"f47ac10b-58cc-4372-a567\"").is_err());
+    assert!(uuid("#uuid \"g47ac10b-58cc-4372-a567-0e02b2c3d479\"").is_err());
+    assert!(uuid("\"f47ac10b-58cc-4372-a567-0e02b2c3d479\"").is_err());
+}
+
+#[test]
+fn test_bytes() {
+    use self::Value::*;
+
+    assert_eq!(bytes("#bytes \"3q2+7w==\"").unwrap(), Bytes(vec![0xde, 0xad, 0xbe, 0xef]));
+    assert_eq!(bytes("#bytes \"\"").unwrap(), Bytes(vec![]));
+
+    // Bytes print as they parse.
+    assert_eq!(Bytes(vec![0xde, 0xad, 0xbe, 0xef]).to_string(), "#bytes \"3q2+7w==\"");
+    assert_eq!(value("#bytes \"3q2+7w==\"").unwrap(), Bytes(vec![0xde, 0xad, 0xbe, 0xef]));
+
+    assert!(bytes("#bytes \"3q2+7w=\"").is_err());
+    assert!(bytes("#bytes \"3q2-7w==\"").is_err());
+    assert!(bytes("\"3q2+7w==\"").is_err());
+}
+
 #[test]
 fn test_text() {
     use self::Value::*;
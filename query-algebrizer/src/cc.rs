@@ -46,13 +46,17 @@ use self::mentat_query::{
 };
 
 /// This enum models the fixed set of default tables we have -- two
-/// tables and two views.
+/// tables and two views -- and the historical views derived from the
+/// transaction log.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum DatomsTable {
     Datoms,             // The non-fulltext datoms table.
     FulltextValues,     // The virtual table mapping IDs to strings.
     FulltextDatoms,     // The fulltext-datoms view.
     AllDatoms,          // Fulltext and non-fulltext datoms.
+    AsOf(Entid),        // All datoms as they were immediately after the given transaction.
+    Since(Entid),       // All current datoms asserted after the given transaction.
+    History,            // Every assertion and retraction in the transaction log.
 }
 
 impl DatomsTable {
@@ -62,6 +66,17 @@ impl DatomsTable {
             DatomsTable::FulltextValues => "fulltext_values",
             DatomsTable::FulltextDatoms => "fulltext_datoms",
             DatomsTable::AllDatoms => "all_datoms",
+            DatomsTable::AsOf(_) => "as_of_datoms",
+            DatomsTable::Since(_) => "since_datoms",
+            DatomsTable::History => "history",
+        }
+    }
+
+    /// Historical views aren't stored tables: they're computed from the transaction log.
+    pub fn is_view(&self) -> bool {
+        match *self {
+            DatomsTable::AsOf(_) | DatomsTable::Since(_) | DatomsTable::History => true,
+            _ => false,
         }
     }
 }
@@ -74,6 +89,7 @@ pub enum DatomsColumn {
     Value,
     Tx,
     ValueTypeTag,
+    Added,          // Only present in `DatomsTable::History`.
 }
 
 impl DatomsColumn {
@@ -85,6 +101,7 @@ impl DatomsColumn {
             Value => "v",
             Tx => "tx",
             ValueTypeTag => "value_type_tag",
+            Added => "added",
         }
     }
 }
@@ -220,6 +237,10 @@ pub struct ConjoiningClauses {
     /// A function used to generate an alias for a table -- e.g., from "datoms" to "datoms123".
     aliaser: TableAliaser,
 
+    /// If set, a historical view -- `DatomsTable::AsOf` or `DatomsTable::Since` -- that replaces
+    /// the current datoms tables for every pattern.
+    view: Option<DatomsTable>,
+
     /// A vector of source/alias pairs used to construct a SQL `FROM` list.
     pub from: Vec<SourceAlias>,
 
@@ -265,6 +286,7 @@ pub enum EmptyBecause {
     InvalidAttributeEntid(Entid),
     InvalidBinding(DatomsColumn, TypedValue),
     ValueTypeMismatch(ValueType, TypedValue),
+    NoRetractionsInView,
     AttributeLookupFailed,         // Catch-all, because the table lookup code is lazy. TODO
 }
 
@@ -292,6 +314,9 @@ impl Debug for EmptyBecause {
                 write!(f, "Type mismatch: {:?} doesn't match attribute type {:?}",
                        typed_value, value_type)
             },
+            &NoRetractionsInView => {
+                write!(f, "Only the history view contains retractions")
+            },
             &AttributeLookupFailed => {
                 write!(f, "Attribute lookup failed")
             },
@@ -303,6 +328,7 @@ impl Debug for ConjoiningClauses {
     fn fmt(&self, fmt: &mut Formatter) -> Result {
         fmt.debug_struct("ConjoiningClauses")
             .field("is_known_empty", &self.is_known_empty)
+            .field("view", &self.view)
             .field("from", &self.from)
             .field("wheres", &self.wheres)
             .field("column_bindings", &self.column_bindings)
@@ -321,6 +347,7 @@ impl Default for ConjoiningClauses {
             is_known_empty: false,
             empty_because: None,
             aliaser: default_table_aliaser(),
+            view: None,
             from: vec![],
             wheres: vec![],
             input_variables: BTreeSet::new(),
@@ -345,13 +372,34 @@ impl ConjoiningClauses {
                     .map(|(k, v)| (k.clone(), v.value_type())));
         cc
     }
+
+    /// Make a CC that matches patterns against the given historical view of the store rather than
+    /// against the current datoms.
+    pub fn with_view(view: DatomsTable) -> ConjoiningClauses {
+        assert!(view.is_view());
+        ConjoiningClauses {
+            view: Some(view),
+            ..Default::default()
+        }
+    }
 }
 
 impl ConjoiningClauses {
-    fn bound_value(&self, var: &Variable) -> Option<TypedValue> {
+    /// The value bound to the given variable ahead of execution, if any.
+    pub fn bound_value(&self, var: &Variable) -> Option<TypedValue> {
         self.value_bindings.get(var).cloned()
     }
 
+    /// Bind the unbound variable to a value that's known without consulting the store.  Columns
+    /// already bound to the variable are constrained to the value.
+    fn bind_var_to_value(&mut self, var: Variable, value: TypedValue) {
+        let columns = self.column_bindings.get(&var).cloned().unwrap_or(vec![]);
+        for QualifiedAlias(table, column) in columns {
+            self.constrain_column_to_constant(table, column, value.clone());
+        }
+        self.value_bindings.insert(var, value);
+    }
+
     pub fn bind_column_to_var(&mut self, schema: &Schema, table: TableAlias, column: DatomsColumn, var: Variable) {
         // Do we have an external binding for this?
         if let Some(bound_val) = self.bound_value(&var) {
@@ -359,7 +407,7 @@ impl ConjoiningClauses {
             // We expect callers to do things like bind keywords here; we need to translate these
             // before they hit our constraints.
             // TODO: recognize when the valueType might be a ref and also translate entids there.
-            if column == DatomsColumn::Value {
+            if column == DatomsColumn::Value || column == DatomsColumn::Added {
                 self.constrain_column_to_constant(table, column, bound_val);
             } else {
                 match bound_val {
@@ -457,13 +505,9 @@ impl ConjoiningClauses {
     }
 
     /// Ensure that the given place has the correct types to be a tx-id.
-    /// Right now this is mostly unimplemented: we fail hard if anything but a placeholder is
-    /// present.
+    /// Transactions are entities, so this is the same as `constrain_to_ref`.
     fn constrain_to_tx(&mut self, tx: &PatternNonValuePlace) {
-        match *tx {
-            PatternNonValuePlace::Placeholder => (),
-            _ => unimplemented!(),           // TODO
-        }
+        self.constrain_to_ref(tx)
     }
 
     /// Ensure that the given place can be an entity, and is congruent with existing types.
@@ -589,9 +633,20 @@ impl ConjoiningClauses {
     fn alias_table<'s, 'a>(&mut self, schema: &'s Schema, pattern: &'a Pattern) -> Option<SourceAlias> {
         self.table_for_places(schema, &pattern.attribute, &pattern.value)
             .when_not(|| assert!(self.is_known_empty))   // table_for_places should have flipped this.
+            .map(|table| self.table_in_view(table))
             .map(|table| SourceAlias(table, (self.aliaser)(table)))
     }
 
+    /// Historical views include fulltext values, so they stand in for both `datoms` and
+    /// `all_datoms`.
+    fn table_in_view(&self, table: DatomsTable) -> DatomsTable {
+        match (self.view, table) {
+            (Some(view), DatomsTable::Datoms) |
+            (Some(view), DatomsTable::AllDatoms) => view,
+            _ => table,
+        }
+    }
+
     fn get_attribute<'s, 'a>(&self, schema: &'s Schema, pattern: &'a Pattern) -> Option<&'s Attribute> {
         match pattern.attribute {
             PatternNonValuePlace::Entid(id) =>
@@ -835,7 +890,11 @@ impl ConjoiningClauses {
             },
             PatternValuePlace::Constant(ref c) => {
                 // TODO: don't allocate.
-                let typed_value = c.clone().into_typed_value();
+                let typed_value = match (value_type, c.clone().into_typed_value()) {
+                    // URIs have no EDN representation of their own, so strings stand in for them.
+                    (Some(ValueType::Uri), TypedValue::String(s)) => TypedValue::Uri(s),
+                    (_, typed_value) => typed_value,
+                };
                 if !typed_value.is_congruent_with(value_type) {
                     // If the attribute and its value don't match, the pattern must fail.
                     // We can never have a congruence failure if `value_type` is `None`, so we
@@ -859,7 +918,12 @@ impl ConjoiningClauses {
                 //
                 // - A long. This is handled by EntidOrInteger.
                 // - A boolean. This is unambiguous.
-                // - A double. This is currently unambiguous, though note that SQLite will equate 5.0 with 5.
+                // - A double. This shares a type tag with longs, and SQLite will equate 5.0 with 5,
+                //   but the translated `HasType` also checks the value's storage class.
+                // - An instant. This is unambiguous.
+                // - A UUID. This is unambiguous.
+                // - Bytes. This is unambiguous.
+                // - A URI. We only produce these when we know the attribute.
                 // - A string. This is unambiguous.
                 // - A keyword. This is unambiguous.
                 //
@@ -872,6 +936,60 @@ impl ConjoiningClauses {
             },
         }
 
+        match pattern.tx {
+            PatternNonValuePlace::Placeholder =>
+                (),
+            PatternNonValuePlace::Variable(ref v) =>
+                self.bind_column_to_var(schema, col.clone(), DatomsColumn::Tx, v.clone()),
+            PatternNonValuePlace::Entid(entid) =>
+                self.constrain_column_to_entity(col.clone(), DatomsColumn::Tx, entid),
+            PatternNonValuePlace::Ident(ref ident) => {
+                if let Some(entid) = self.entid_for_ident(schema, ident) {
+                    self.constrain_column_to_entity(col.clone(), DatomsColumn::Tx, entid)
+                } else {
+                    // A resolution failure means we're done here.
+                    self.mark_known_empty(EmptyBecause::UnresolvedIdent(ident.clone()));
+                    return;
+                }
+            }
+        }
+
+        // Only the history view records retractions.  Everywhere else every datom is an
+        // assertion, so there's no `added` column to constrain.
+        let has_retractions = alias.0 == DatomsTable::History;
+        match pattern.added {
+            PatternValuePlace::Placeholder =>
+                (),
+            PatternValuePlace::Constant(NonIntegerConstant::Boolean(added)) => {
+                if has_retractions {
+                    self.constrain_column_to_constant(col.clone(), DatomsColumn::Added, TypedValue::Boolean(added));
+                } else if !added {
+                    self.mark_known_empty(EmptyBecause::NoRetractionsInView);
+                }
+            },
+            PatternValuePlace::Variable(ref v) => {
+                self.constrain_var_to_type(v.clone(), ValueType::Boolean);
+                if self.is_known_empty {
+                    return;
+                }
+                if has_retractions {
+                    self.bind_column_to_var(schema, col.clone(), DatomsColumn::Added, v.clone());
+                } else {
+                    // Every datom in this view is an assertion.
+                    match self.bound_value(v) {
+                        Some(TypedValue::Boolean(false)) => self.mark_known_empty(EmptyBecause::NoRetractionsInView),
+                        Some(_) => (),
+                        None => self.bind_var_to_value(v.clone(), TypedValue::Boolean(true)),
+                    }
+                }
+            },
+            PatternValuePlace::EntidOrInteger(i) =>
+                self.mark_known_empty(EmptyBecause::ValueTypeMismatch(ValueType::Boolean, TypedValue::Long(i))),
+            PatternValuePlace::IdentOrKeyword(ref kw) =>
+                self.mark_known_empty(EmptyBecause::ValueTypeMismatch(ValueType::Boolean, TypedValue::Keyword(kw.clone()))),
+            PatternValuePlace::Constant(ref c) =>
+                self.mark_known_empty(EmptyBecause::ValueTypeMismatch(ValueType::Boolean, c.clone().into_typed_value())),
+        }
     }
 
     pub fn apply_pattern<'s, 'p>(&mut self, schema: &'s Schema, pattern: &'p Pattern) {
@@ -918,6 +1036,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Constant(NonIntegerConstant::Boolean(true)),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         assert!(cc.is_known_empty);
@@ -936,6 +1055,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Constant(NonIntegerConstant::Boolean(true)),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         assert!(cc.is_known_empty);
@@ -959,6 +1079,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Constant(NonIntegerConstant::Boolean(true)),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // println!("{:#?}", cc);
@@ -987,6 +1108,92 @@ mod testing {
         ]);
     }
 
+    #[test]
+    fn test_apply_pattern_in_view() {
+        let mut schema = Schema::default();
+
+        associate_ident(&mut schema, NamespacedKeyword::new("foo", "bar"), 99);
+        add_attribute(&mut schema, 99, Attribute {
+            value_type: ValueType::String,
+            fulltext: true,
+            ..Default::default()
+        });
+
+        let x = Variable(PlainSymbol::new("?x"));
+        let y = Variable(PlainSymbol::new("?y"));
+        let patterns = vec![
+            // Would use `datoms`.
+            Pattern {
+                source: None,
+                entity: PatternNonValuePlace::Variable(x.clone()),
+                attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
+                value: PatternValuePlace::Placeholder,
+                tx: PatternNonValuePlace::Placeholder,
+                added: PatternValuePlace::Placeholder,
+            },
+            // Would use `all_datoms`.
+            Pattern {
+                source: None,
+                entity: PatternNonValuePlace::Variable(x.clone()),
+                attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
+                value: PatternValuePlace::Variable(y.clone()),
+                tx: PatternNonValuePlace::Placeholder,
+                added: PatternValuePlace::Placeholder,
+            },
+        ];
+
+        for &(view, ref prefix) in &[(DatomsTable::AsOf(0x10000001), "as_of_datoms"),
+                                     (DatomsTable::Since(0x10000001), "since_datoms")] {
+            let mut cc = ConjoiningClauses::with_view(view);
+            for pattern in &patterns {
+                cc.apply_pattern(&schema, pattern);
+            }
+
+            assert!(!cc.is_known_empty);
+            assert_eq!(cc.from, vec![SourceAlias(view, format!("{}00", prefix)),
+                                     SourceAlias(view, format!("{}01", prefix))]);
+        }
+    }
+
+    #[test]
+    fn test_apply_added_variable_outside_history() {
+        let mut cc = ConjoiningClauses::default();
+        let mut schema = Schema::default();
+
+        associate_ident(&mut schema, NamespacedKeyword::new("foo", "bar"), 99);
+        add_attribute(&mut schema, 99, Attribute {
+            value_type: ValueType::Boolean,
+            ..Default::default()
+        });
+
+        let x = Variable(PlainSymbol::new("?x"));
+        let b = Variable(PlainSymbol::new("?b"));
+        cc.apply_pattern(&schema, &Pattern {
+            source: None,
+            entity: PatternNonValuePlace::Variable(x.clone()),
+            attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
+            value: PatternValuePlace::Variable(b.clone()),
+            tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
+        });
+        cc.apply_pattern(&schema, &Pattern {
+            source: None,
+            entity: PatternNonValuePlace::Variable(x.clone()),
+            attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
+            value: PatternValuePlace::Placeholder,
+            tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Variable(b.clone()),
+        });
+
+        assert!(!cc.is_known_empty);
+
+        // Every current datom is an assertion, so ?b is `true`, and so is the value it's bound to.
+        let d0_v = QualifiedAlias("datoms00".to_string(), DatomsColumn::Value);
+        assert_eq!(cc.bound_value(&b), Some(TypedValue::Boolean(true)));
+        assert_eq!(cc.known_types.get(&b), Some(&ValueType::Boolean));
+        assert!(cc.wheres.contains(&ColumnConstraint::EqualsValue(d0_v, TypedValue::Boolean(true))));
+    }
+
     #[test]
     fn test_apply_unattributed_pattern() {
         let mut cc = ConjoiningClauses::default();
@@ -999,6 +1206,7 @@ mod testing {
             attribute: PatternNonValuePlace::Placeholder,
             value: PatternValuePlace::Constant(NonIntegerConstant::Boolean(true)),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // println!("{:#?}", cc);
@@ -1048,6 +1256,7 @@ mod testing {
             attribute: PatternNonValuePlace::Variable(a.clone()),
             value: PatternValuePlace::Variable(v.clone()),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // println!("{:#?}", cc);
@@ -1087,6 +1296,7 @@ mod testing {
             attribute: PatternNonValuePlace::Variable(a.clone()),
             value: PatternValuePlace::Variable(v.clone()),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         assert!(cc.is_known_empty);
@@ -1109,6 +1319,7 @@ mod testing {
             attribute: PatternNonValuePlace::Variable(a.clone()),
             value: PatternValuePlace::Variable(v.clone()),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // println!("{:#?}", cc);
@@ -1139,6 +1350,7 @@ mod testing {
             attribute: PatternNonValuePlace::Placeholder,
             value: PatternValuePlace::Constant(NonIntegerConstant::Text("hello".to_string())),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // println!("{:#?}", cc);
@@ -1190,6 +1402,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "roz")),
             value: PatternValuePlace::Constant(NonIntegerConstant::Text("idgoeshere".to_string())),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
         cc.apply_pattern(&schema, &Pattern {
             source: None,
@@ -1197,6 +1410,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Variable(y.clone()),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // Finally, expand column bindings to get the overlaps for ?x.
@@ -1262,6 +1476,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Variable(y.clone()),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         let d0_e = QualifiedAlias("datoms00".to_string(), DatomsColumn::Entity);
@@ -1305,6 +1520,7 @@ mod testing {
             attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Variable(y.clone()),
             tx: PatternNonValuePlace::Placeholder,
+            added: PatternValuePlace::Placeholder,
         });
 
         // The type of the provided binding doesn't match the type of the attribute.
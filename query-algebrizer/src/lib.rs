@@ -58,9 +58,18 @@ impl AlgebraicQuery {
 
 #[allow(dead_code)]
 pub fn algebrize(schema: &Schema, parsed: FindQuery) -> AlgebraicQuery {
+    algebrize_with_cc(schema, parsed, cc::ConjoiningClauses::default())
+}
+
+/// Algebrize a query against a historical view of the store -- `DatomsTable::AsOf` or
+/// `DatomsTable::Since` -- rather than against the current datoms.
+pub fn algebrize_with_view(schema: &Schema, parsed: FindQuery, view: DatomsTable) -> AlgebraicQuery {
+    algebrize_with_cc(schema, parsed, cc::ConjoiningClauses::with_view(view))
+}
+
+fn algebrize_with_cc(schema: &Schema, parsed: FindQuery, mut cc: cc::ConjoiningClauses) -> AlgebraicQuery {
     // TODO: integrate default source into pattern processing.
     // TODO: flesh out the rest of find-into-context.
-    let mut cc = cc::ConjoiningClauses::default();
     let where_clauses = parsed.where_clauses;
     for where_clause in where_clauses {
         if let WhereClause::Pattern(p) = where_clause {
@@ -233,6 +233,7 @@ mod test_parse {
                        attribute: PatternNonValuePlace::Ident(NamespacedKeyword::new("foo", "bar")),
                        value: PatternValuePlace::Variable(Variable(PlainSymbol::new("?y"))),
                        tx: PatternNonValuePlace::Placeholder,
+                       added: PatternValuePlace::Placeholder,
                    })]);
 
     }
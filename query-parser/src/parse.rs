@@ -111,15 +111,17 @@ def_value_parser_fn!(Where, pattern, Pattern, input, {
                  Where::pattern_non_value_place(),              // a
                  optional(Where::pattern_value_place()),        // v
                  optional(Where::pattern_non_value_place()),    // tx
+                 optional(Where::pattern_value_place()),        // added
                  eof())
-                .map(|(src, e, a, v, tx, _)| {
+                .map(|(src, e, a, v, tx, added, _)| {
                     let v = v.unwrap_or(PatternValuePlace::Placeholder);
                     let tx = tx.unwrap_or(PatternNonValuePlace::Placeholder);
+                    let added = added.unwrap_or(PatternValuePlace::Placeholder);
 
                     // Pattern::new takes care of reversal of reversed
                     // attributes: [?x :foo/_bar ?y] turns into
                     // [?y :foo/bar ?x].
-                    Pattern::new(src, e, a, v, tx)
+                    Pattern::new(src, e, a, v, tx, added)
                 });
 
             // This is a bit messy: the inner conversion to a Pattern can
@@ -283,6 +285,7 @@ mod test {
             attribute: PatternNonValuePlace::Ident(a),
             value: PatternValuePlace::Constant(NonIntegerConstant::Float(v)),
             tx: PatternNonValuePlace::Variable(Variable(tx)),
+            added: PatternValuePlace::Placeholder,
         });
     }
 
@@ -305,6 +308,28 @@ mod test {
             attribute: PatternNonValuePlace::Variable(Variable(a)),
             value: PatternValuePlace::Variable(Variable(v)),
             tx: PatternNonValuePlace::Variable(Variable(tx)),
+            added: PatternValuePlace::Placeholder,
+        });
+    }
+
+    #[test]
+    fn test_pattern_added() {
+        let e = edn::PlainSymbol::new("?e");
+        let a = edn::NamespacedKeyword::new("foo", "bar");
+        let tx = edn::PlainSymbol::new("?tx");
+        let input = [edn::Value::Vector(
+            vec!(edn::Value::PlainSymbol(e.clone()),
+                 edn::Value::NamespacedKeyword(a.clone()),
+                 edn::Value::PlainSymbol(edn::PlainSymbol::new("_")),
+                 edn::Value::PlainSymbol(tx.clone()),
+                 edn::Value::Boolean(false)))];
+        assert_parses_to!(Where::pattern, input, Pattern {
+            source: None,
+            entity: PatternNonValuePlace::Variable(Variable(e)),
+            attribute: PatternNonValuePlace::Ident(a),
+            value: PatternValuePlace::Placeholder,
+            tx: PatternNonValuePlace::Variable(Variable(tx)),
+            added: PatternValuePlace::Constant(NonIntegerConstant::Boolean(false)),
         });
     }
 
@@ -345,6 +370,7 @@ mod test {
             attribute: PatternNonValuePlace::Ident(edn::NamespacedKeyword::new("foo", "bar")),
             value: PatternValuePlace::Placeholder,
             tx: PatternNonValuePlace::Variable(Variable(tx)),
+            added: PatternValuePlace::Placeholder,
         });
     }
 
@@ -208,6 +208,15 @@ fn project_elements<'a, I: IntoIterator<Item = &'a Element>>(
             // into the SQL projection, aliased to the name of the variable,
             // and we push an annotated index into the projector.
             &Element::Variable(ref var) => {
+                // A variable whose value is known ahead of execution is projected as a constant.
+                if let Some(value) = query.cc.bound_value(var) {
+                    let tag = value.value_type().value_type_tag();
+                    cols.push(ProjectedColumn(ColumnOrExpression::Value(value), column_name(var)));
+                    templates.push(TypedIndex::Known(i, tag));
+                    i += 1;     // We used one SQL column.
+                    continue;
+                }
+
                 // Every variable should be bound by the top-level CC to at least
                 // one column in the query. If that constraint is violated it's a
                 // bug in our code, so it's appropriate to panic here.
@@ -20,6 +20,7 @@ use mentat_core::{
 
 use mentat_query_algebrizer::{
     DatomsColumn,
+    DatomsTable,
     QualifiedAlias,
     SourceAlias,
 };
@@ -61,6 +62,22 @@ pub enum Projection {
 #[derive(Copy, Clone)]
 pub struct Op(&'static str);      // TODO: we can do better than this!
 
+/// The SQLite storage classes that `typeof` can report for a value.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SQLStorageClass {
+    Integer,
+    Real,
+}
+
+impl SQLStorageClass {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            SQLStorageClass::Integer => "integer",
+            SQLStorageClass::Real => "real",
+        }
+    }
+}
+
 pub enum Constraint {
     Infix {
         op: Op,
@@ -73,7 +90,11 @@ pub enum Constraint {
     In {
         left: ColumnOrExpression,
         list: Vec<ColumnOrExpression>,
-    }
+    },
+    TypeCheck {
+        value: ColumnOrExpression,
+        storage_class: SQLStorageClass,
+    },
 }
 
 impl Constraint {
@@ -258,6 +279,15 @@ impl QueryFragment for Constraint {
                 out.push_sql(")");
                 Ok(())
             },
+
+            &TypeCheck { ref value, storage_class } => {
+                out.push_sql("typeof(");
+                value.push_sql(out)?;
+                out.push_sql(") = '");
+                out.push_sql(storage_class.as_str());
+                out.push_sql("'");
+                Ok(())
+            },
         }
     }
 }
@@ -269,10 +299,51 @@ impl QueryFragment for JoinOp {
     }
 }
 
+/// Fulltext values are stored in the `transactions` log as `fulltext_values` rowids; views over the
+/// log interpolate the strings just as the `all_datoms` view does.
+const TRANSACTIONS_VALUE_SQL: &'static str =
+    r#"CASE WHEN t.value_type_tag = 10 AND typeof(t.v) = 'integer'
+            THEN (SELECT text FROM fulltext_values WHERE rowid = t.v)
+            ELSE t.v END"#;
+
+/// The datoms present immediately after transaction `tx`, rebuilt from the `transactions` log: a
+/// datom is present if it was asserted at or before `tx` and not retracted again by `tx`.
+fn push_as_of_sql(out: &mut QueryBuilder, tx: Entid) {
+    out.push_sql(format!(
+        r#"(SELECT t.e AS e, t.a AS a, {v} AS v, t.tx AS tx, t.value_type_tag AS value_type_tag
+              FROM transactions AS t
+              WHERE t.tx <= {tx} AND t.added = 1
+                AND NOT EXISTS (SELECT 1 FROM transactions AS later
+                                WHERE later.e = t.e AND later.a = t.a
+                                  AND later.value_type_tag = t.value_type_tag AND later.v = t.v
+                                  AND later.tx > t.tx AND later.tx <= {tx}))"#,
+        v = TRANSACTIONS_VALUE_SQL,
+        tx = tx).as_str());
+}
+
+/// The current datoms that were asserted after transaction `tx`.
+fn push_since_sql(out: &mut QueryBuilder, tx: Entid) {
+    out.push_sql(format!(
+        "(SELECT e, a, v, tx, value_type_tag FROM all_datoms WHERE tx > {})", tx).as_str());
+}
+
+/// Every row of the `transactions` log, assertions and retractions alike.
+fn push_history_sql(out: &mut QueryBuilder) {
+    out.push_sql(format!(
+        r#"(SELECT t.e AS e, t.a AS a, {v} AS v, t.tx AS tx, t.value_type_tag AS value_type_tag, t.added AS added
+              FROM transactions AS t)"#,
+        v = TRANSACTIONS_VALUE_SQL).as_str());
+}
+
 // We don't own SourceAlias or QueryFragment, so we can't implement the trait.
 fn source_alias_push_sql(out: &mut QueryBuilder, sa: &SourceAlias) -> BuildQueryResult {
     let &SourceAlias(ref table, ref alias) = sa;
-    out.push_identifier(table.name())?;
+    match *table {
+        DatomsTable::AsOf(tx) => push_as_of_sql(out, tx),
+        DatomsTable::Since(tx) => push_since_sql(out, tx),
+        DatomsTable::History => push_history_sql(out),
+        _ => out.push_identifier(table.name())?,
+    }
     out.push_sql(" AS ");
     out.push_identifier(alias.as_str())
 }
@@ -361,7 +432,6 @@ impl SelectQuery {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mentat_query_algebrizer::DatomsTable;
 
     fn build_constraint(c: Constraint) -> String {
         let mut builder = SQLiteQueryBuilder::new();
@@ -424,6 +494,16 @@ mod tests {
         assert_eq!("((123 = 456 AND 789 = 246))", build_constraint(c));
     }
 
+    #[test]
+    fn test_type_check_constraint() {
+        let c = Constraint::TypeCheck {
+            value: ColumnOrExpression::Column(QualifiedAlias("datoms01".to_string(), DatomsColumn::Value)),
+            storage_class: SQLStorageClass::Real,
+        };
+
+        assert_eq!("typeof(`datoms01`.v) = 'real'", build_constraint(c));
+    }
+
     #[test]
     fn test_end_to_end() {
 
@@ -468,4 +548,23 @@ mod tests {
         assert_eq!("SELECT `datoms00`.e AS `x` FROM `datoms` AS `datoms00`, `datoms` AS `datoms01` WHERE `datoms01`.v = `datoms00`.v AND `datoms00`.a = 65537 AND `datoms01`.a = 65536", sql);
         assert!(args.is_empty());
     }
+
+    #[test]
+    fn test_since_source() {
+        let query = SelectQuery {
+            projection: Projection::Columns(
+                            vec![
+                                ProjectedColumn(
+                                    ColumnOrExpression::Column(QualifiedAlias("since_datoms00".to_string(), DatomsColumn::Entity)),
+                                    "x".to_string()),
+                            ]),
+            from: FromClause::TableList(TableList(vec![SourceAlias(DatomsTable::Since(268435457), "since_datoms00".to_string())])),
+            constraints: vec![],
+            limit: None,
+        };
+
+        let SQLQuery { sql, args } = query.to_sql_query().unwrap();
+        assert_eq!("SELECT `since_datoms00`.e AS `x` FROM (SELECT e, a, v, tx, value_type_tag FROM all_datoms WHERE tx > 268435457) AS `since_datoms00`", sql);
+        assert!(args.is_empty());
+    }
 }
@@ -47,6 +47,7 @@ use mentat_query_sql::{
     Projection,
     ProjectedColumn,
     SelectQuery,
+    SQLStorageClass,
     TableList,
 };
 
@@ -110,8 +111,32 @@ impl ToConstraint for ColumnConstraint {
             },
 
             HasType(table, value_type) => {
-                let column = QualifiedAlias(table, DatomsColumn::ValueTypeTag).to_column();
-                Constraint::equal(column, ColumnOrExpression::Integer(value_type.value_type_tag()))
+                let tag_column = QualifiedAlias(table.clone(), DatomsColumn::ValueTypeTag).to_column();
+                let has_tag = Constraint::equal(tag_column,
+                                                ColumnOrExpression::Integer(value_type.value_type_tag()));
+
+                // Longs and doubles share a type tag, so we tell them apart by the storage class
+                // SQLite reports for the value itself.
+                let storage_class = match value_type {
+                    ValueType::Long => Some(SQLStorageClass::Integer),
+                    ValueType::Double => Some(SQLStorageClass::Real),
+                    _ => None,
+                };
+                match storage_class {
+                    Some(storage_class) => {
+                        let value_column = QualifiedAlias(table, DatomsColumn::Value).to_column();
+                        Constraint::And {
+                            constraints: vec![
+                                has_tag,
+                                Constraint::TypeCheck {
+                                    value: value_column,
+                                    storage_class: storage_class,
+                                },
+                            ],
+                        }
+                    },
+                    None => has_tag,
+                }
             },
         }
     }
@@ -127,8 +127,8 @@ fn test_unknown_attribute_double_value() {
     let SQLQuery { sql, args } = translate(&schema, input, None);
 
     // In general, doubles _could_ be 1.0, which might match a boolean or a ref. Set tag = 5 to
-    // make sure we only match numbers.
-    assert_eq!(sql, "SELECT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.v = 9.95 AND `datoms00`.value_type_tag = 5");
+    // make sure we only match numbers, and check the storage class so that we don't match longs.
+    assert_eq!(sql, "SELECT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.v = 9.95 AND (`datoms00`.value_type_tag = 5 AND typeof(`datoms00`.v) = 'real')");
     assert_eq!(args, vec![]);
 }
 
@@ -34,7 +34,7 @@ extern crate edn;
 extern crate mentat_core;
 
 use std::fmt;
-use edn::{BigInt, OrderedFloat};
+use edn::{BigInt, DateTime, OrderedFloat, Utc, Uuid};
 pub use edn::{NamespacedKeyword, PlainSymbol};
 use mentat_core::TypedValue;
 
@@ -107,6 +107,9 @@ pub enum NonIntegerConstant {
     Boolean(bool),
     BigInteger(BigInt),
     Float(OrderedFloat<f64>),
+    Instant(DateTime<Utc>),
+    Uuid(Uuid),
+    Bytes(Vec<u8>),
     Text(String),
 }
 
@@ -116,6 +119,9 @@ impl NonIntegerConstant {
             NonIntegerConstant::BigInteger(_) => unimplemented!(),     // TODO: #280.
             NonIntegerConstant::Boolean(v) => TypedValue::Boolean(v),
             NonIntegerConstant::Float(v) => TypedValue::Double(v),
+            NonIntegerConstant::Instant(v) => TypedValue::instant(v),
+            NonIntegerConstant::Uuid(v) => TypedValue::Uuid(v),
+            NonIntegerConstant::Bytes(v) => TypedValue::Bytes(v),
             NonIntegerConstant::Text(v) => TypedValue::String(v),
         }
     }
@@ -224,6 +230,12 @@ impl FromValue<PatternValuePlace> for PatternValuePlace {
                 Some(PatternValuePlace::Constant(NonIntegerConstant::Float(x))),
             &edn::Value::BigInteger(ref x) =>
                 Some(PatternValuePlace::Constant(NonIntegerConstant::BigInteger(x.clone()))),
+            &edn::Value::Instant(x) =>
+                Some(PatternValuePlace::Constant(NonIntegerConstant::Instant(x))),
+            &edn::Value::Uuid(x) =>
+                Some(PatternValuePlace::Constant(NonIntegerConstant::Uuid(x))),
+            &edn::Value::Bytes(ref x) =>
+                Some(PatternValuePlace::Constant(NonIntegerConstant::Bytes(x.clone()))),
             &edn::Value::Text(ref x) =>
                 Some(PatternValuePlace::Constant(NonIntegerConstant::Text(x.clone()))),
             _ => None,
@@ -395,6 +407,9 @@ pub struct Pattern {
     pub attribute: PatternNonValuePlace,
     pub value: PatternValuePlace,
     pub tx: PatternNonValuePlace,
+
+    /// Whether the datom was asserted or retracted.  Only history views contain retractions.
+    pub added: PatternValuePlace,
 }
 
 impl Pattern {
@@ -402,7 +417,8 @@ impl Pattern {
                e: PatternNonValuePlace,
                a: PatternNonValuePlace,
                v: PatternValuePlace,
-               tx: PatternNonValuePlace) -> Option<Pattern> {
+               tx: PatternNonValuePlace,
+               added: PatternValuePlace) -> Option<Pattern> {
         let aa = a.clone();       // Too tired of fighting borrow scope for now.
         if let PatternNonValuePlace::Ident(ref k) = aa {
             if k.is_backward() {
@@ -417,6 +433,7 @@ impl Pattern {
                         attribute: PatternNonValuePlace::Ident(k.to_reversed()),
                         value: e_v,
                         tx: tx,
+                        added: added,
                     });
                 } else {
                     return None;
@@ -429,6 +446,7 @@ impl Pattern {
             attribute: a,
             value: v,
             tx: tx,
+            added: added,
         })
     }
 }
@@ -15,7 +15,10 @@ extern crate mentat_core;
 
 use ordered_float::OrderedFloat;
 
-use mentat_core::TypedValue;
+use mentat_core::{
+    ToMicros,
+    TypedValue,
+};
 
 error_chain! {
     types {
@@ -134,8 +137,16 @@ impl QueryBuilder for SQLiteQueryBuilder {
             &Boolean(v) => self.push_sql(if v { "1" } else { "0" }),
             &Long(v) => self.push_sql(v.to_string().as_str()),
             &Double(OrderedFloat(v)) => self.push_sql(v.to_string().as_str()),
+            &Instant(v) => self.push_sql(v.to_micros().to_string().as_str()),
             &String(ref s) => self.push_static_arg(s.clone()),
             &Keyword(ref s) => self.push_static_arg(s.to_string()),
+            // UUIDs are stored as 16-byte blobs; a hex blob literal matches them exactly.
+            &Uuid(ref u) => self.push_sql(format!("X'{}'", u.simple()).as_str()),
+            &Uri(ref s) => self.push_static_arg(s.clone()),
+            &Bytes(ref b) => {
+                let hex: std::string::String = b.iter().map(|x| format!("{:02x}", x)).collect();
+                self.push_sql(format!("X'{}'", hex).as_str())
+            },
         }
         Ok(())
     }
@@ -10,26 +10,45 @@
 
 #![allow(dead_code)]
 
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+    HashMap,
+};
+use std::panic::{
+    self,
+    AssertUnwindSafe,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+};
 
 use rusqlite;
 
 use edn;
 use errors::*;
 use mentat_core::{
+    Entid,
     Schema,
     TypedValue,
 };
 use mentat_db::db;
 use mentat_db::{
     transact,
+    tx_range,
     PartitionMap,
+    TxRange,
     TxReport,
 };
+use mentat_tx::entities::Entity;
 use mentat_tx_parser;
 use query::{
+    q_as_of,
+    q_history,
     q_once,
+    q_since,
     QueryResults,
 };
 
@@ -57,8 +76,37 @@ impl Metadata {
     }
 }
 
+/// The number of times `Conn::transact` tries to take the SQLite write lock before giving up.
+///
+/// Each attempt waits for up to the connection's busy timeout, so this only bounds how long a
+/// writer waits when other connections hold the write lock for a long time.
+const MAX_TRANSACT_ATTEMPTS: usize = 5;
+
+/// Identifies a transaction listener registered with a `Conn`, so that it can be unregistered.
+pub type TxListenerKey = usize;
+
+/// A transaction listener: a callback, and the attributes whose changes it is interested in.
+struct TxListener {
+    attributes: BTreeSet<Entid>,
+    callback: Box<Fn(&TxReport) + Send + Sync>,
+}
+
+impl TxListener {
+    /// Return `true` if the given committed transaction changed any of this listener's attributes.
+    fn is_interested_in(&self, report: &TxReport) -> bool {
+        report.tx_data.iter().any(|datom| self.attributes.contains(&datom.a))
+    }
+}
+
+/// The transaction listeners registered with a `Conn`.
+#[derive(Default)]
+struct TxListeners {
+    next_key: TxListenerKey,
+    listeners: BTreeMap<TxListenerKey, Arc<TxListener>>,
+}
+
 /// A mutable, safe reference to the current Mentat store.
-struct Conn {
+pub struct Conn {
     /// `Mutex` since all reads and writes need to be exclusive.  Internally, owned data for the
     /// volatile parts (generation and partition map), and `Arc` for the infrequently changing parts
     /// (schema) that we want to share across threads.  A consuming thread may use a shared
@@ -69,7 +117,9 @@ struct Conn {
     /// map and schema -- forward.
     metadata: Mutex<Metadata>,
 
-    // TODO: maintain set of change listeners or handles to transaction report queues. #298.
+    /// Listeners notified after each committed transaction.  Separate from `metadata` so that
+    /// listeners are never invoked while the metadata is locked.
+    tx_listeners: Mutex<TxListeners>,
 
     // TODO: maintain cache of query plans that could be shared across threads and invalidated when
     // the schema changes. #315.
@@ -79,7 +129,8 @@ impl Conn {
     // Intentionally not public.
     fn new(partition_map: PartitionMap, schema: Schema) -> Conn {
         Conn {
-            metadata: Mutex::new(Metadata::new(0, partition_map, Arc::new(schema)))
+            metadata: Mutex::new(Metadata::new(0, partition_map, Arc::new(schema))),
+            tx_listeners: Mutex::new(TxListeners::default()),
         }
     }
 
@@ -123,9 +174,124 @@ impl Conn {
                limit)
     }
 
+    /// Query the Mentat store as it was immediately after the transaction `tx` was committed.
+    pub fn q_as_of<T, U>(&self,
+                         sqlite: &rusqlite::Connection,
+                         tx: Entid,
+                         query: &str,
+                         inputs: T,
+                         limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        q_as_of(sqlite,
+                &*self.current_schema(),
+                tx,
+                query,
+                inputs,
+                limit)
+    }
+
+    /// Query only those current datoms in the Mentat store that were asserted after the
+    /// transaction `tx`.
+    pub fn q_since<T, U>(&self,
+                         sqlite: &rusqlite::Connection,
+                         tx: Entid,
+                         query: &str,
+                         inputs: T,
+                         limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        q_since(sqlite,
+                &*self.current_schema(),
+                tx,
+                query,
+                inputs,
+                limit)
+    }
+
+    /// Query every assertion and retraction in the Mentat store's transaction log.
+    pub fn q_history<T, U>(&self,
+                           sqlite: &rusqlite::Connection,
+                           query: &str,
+                           inputs: T,
+                           limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        q_history(sqlite,
+                  &*self.current_schema(),
+                  query,
+                  inputs,
+                  limit)
+    }
+
+    /// Read the transactions with IDs in `[start, end)` from the transaction log, using the given
+    /// connection.  Attributes are named according to the current schema.
+    pub fn tx_range<'c, T>(&self,
+                           sqlite: &'c rusqlite::Connection,
+                           start: Entid,
+                           end: T) -> TxRange<'c, Arc<Schema>>
+        where T: Into<Option<Entid>>
+        {
+
+        tx_range(sqlite, self.current_schema(), start, end)
+    }
+
+    /// Begin a read-only view of the Mentat store, using the given connection.  Queries against the
+    /// view see the store and the metadata as they were when the view began, even if other writers
+    /// commit in the meantime.
+    pub fn begin_read<'a>(&self, sqlite: &'a mut rusqlite::Connection) -> Result<InProgressRead<'a>> {
+        let transaction = sqlite.transaction()?;
+
+        // SQLite takes a deferred transaction's snapshot at its first read, which here is the read
+        // of the partition map, so the snapshot agrees with the metadata.
+        let metadata = self.lock_current_metadata(&transaction)?;
+
+        Ok(InProgressRead {
+            transaction: transaction,
+            generation: metadata.generation,
+            partition_map: metadata.partition_map.clone(),
+            schema: metadata.schema.clone(),
+        })
+    }
+
+    /// Begin a writable view of the Mentat store, using the given connection.  The view holds the
+    /// SQLite write lock, so no other writer can commit until the view is committed or dropped.
+    /// Dropping the view without committing rolls it back.
+    pub fn begin_write<'a, 'c>(&'c self, sqlite: &'a mut rusqlite::Connection) -> Result<InProgress<'a, 'c>> {
+        // Take the SQLite write lock up front, so that writers through this `Conn` are serialized:
+        // the metadata read below is current until we commit.
+        let transaction = sqlite.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+        let metadata = self.lock_current_metadata(&transaction)?;
+
+        Ok(InProgress {
+            conn: self,
+            transaction: transaction,
+            generation: metadata.generation,
+            // Expensive, but the partition map is updated after every committed transaction.
+            partition_map: metadata.partition_map.clone(),
+            // Cheap.
+            schema: metadata.schema.clone(),
+            tx_reports: vec![],
+        })
+    }
+
     /// Transact entities against the Mentat store, using the given connection and the current
     /// metadata.
-    pub fn transact(&mut self,
+    ///
+    /// Writers take the SQLite write lock before reading the metadata, so transactions through
+    /// this `Conn` are serialized; if another connection has written to the store in the meantime,
+    /// the metadata is first reloaded from the store.
+    ///
+    /// If the store stays busy with other writers, the transaction is attempted up to
+    /// `MAX_TRANSACT_ATTEMPTS` times before failing with `TransactRaceLost`.
+    pub fn transact(&self,
                     sqlite: &mut rusqlite::Connection,
                     transaction: &str) -> Result<TxReport> {
 
@@ -133,57 +299,292 @@ impl Conn {
             .map(|x| x.without_spans())?;
         let entities = mentat_tx_parser::Tx::parse(&[assertion_vector][..])?;
 
-        let tx = sqlite.transaction()?;
+        for _ in 0..MAX_TRANSACT_ATTEMPTS {
+            match self.transact_entities(sqlite, entities.clone()) {
+                // Nothing was committed; try again.
+                Err(Error(ErrorKind::Rusqlite(rusqlite::Error::SqliteFailure(ref e, _)), _)) if e.code == rusqlite::ErrorCode::DatabaseBusy => continue,
+                result => return result,
+            }
+        }
+
+        bail!(ErrorKind::TransactRaceLost(MAX_TRANSACT_ATTEMPTS))
+    }
+
+    fn transact_entities(&self,
+                         sqlite: &mut rusqlite::Connection,
+                         entities: Vec<Entity>) -> Result<TxReport> {
+        let mut in_progress = self.begin_write(sqlite)?;
+        let report = in_progress.transact_entities(entities)?;
+        in_progress.commit()?;
+        Ok(report)
+    }
+
+    /// Lock and return the metadata, first reloading it from the store if another connection has
+    /// committed since this `Conn` last saw it.  Every committed transaction allocates a tx id, so
+    /// the store's `parts` table tells us.
+    ///
+    /// The caller must be in a SQLite transaction, so that the store as the caller sees it can't
+    /// move on again until the caller commits or rolls back.
+    fn lock_current_metadata(&self, sqlite: &rusqlite::Connection) -> Result<MutexGuard<Metadata>> {
+        // Writers through this `Conn` commit while holding the mutex, so we take it before reading.
+        let mut metadata = self.metadata.lock().unwrap();
+
+        let stored_partition_map = db::read_partition_map(sqlite)?;
+        if metadata.partition_map != stored_partition_map {
+            let db = db::read_db(sqlite)?;
+            metadata.generation += 1;
+            metadata.partition_map = db.partition_map;
+            metadata.schema = Arc::new(db.schema);
+        }
+
+        Ok(metadata)
+    }
+
+    /// Revert the transaction `tx`, using the given connection: transact the inverse of each datom
+    /// it asserted or retracted, as a new transaction whose :db.revert/tx is `tx`.
+    ///
+    /// Fails if `tx` changed any :db/noHistory attribute, or if a later transaction changed any of
+    /// the same [e a] pairs, unless `force` is true.
+    pub fn revert(&self,
+                  sqlite: &mut rusqlite::Connection,
+                  tx: Entid,
+                  force: bool) -> Result<TxReport> {
+
+        let mut in_progress = self.begin_write(sqlite)?;
+        let report = in_progress.revert(tx, force)?;
+        in_progress.commit()?;
+        Ok(report)
+    }
+
+    /// Register a listener to be called synchronously, on the transacting thread, with the report
+    /// of each committed transaction that changes any of the given `attributes`.
+    ///
+    /// A listener that panics doesn't fail the transaction, which is already committed, and doesn't
+    /// prevent other listeners from being notified.
+    pub fn register_tx_listener<F>(&self, attributes: BTreeSet<Entid>, callback: F) -> TxListenerKey
+        where F: Fn(&TxReport) + Send + Sync + 'static {
+        let mut tx_listeners = self.tx_listeners.lock().unwrap();
+        let key = tx_listeners.next_key;
+        tx_listeners.next_key += 1;
+        tx_listeners.listeners.insert(key, Arc::new(TxListener {
+            attributes: attributes,
+            callback: Box::new(callback),
+        }));
+        key
+    }
+
+    /// Register a listener that delivers the report of each committed transaction that changes any
+    /// of the given `attributes` through the returned channel.  Reports are no longer delivered once
+    /// the receiver is dropped.
+    pub fn register_tx_channel(&self, attributes: BTreeSet<Entid>) -> (TxListenerKey, Receiver<TxReport>) {
+        let (sender, receiver) = channel();
+        let sender = Mutex::new(sender);
+        let key = self.register_tx_listener(attributes, move |report| {
+            let _ = sender.lock().unwrap().send(report.clone());
+        });
+        (key, receiver)
+    }
+
+    /// Unregister the listener with the given key.  Returns `true` if the listener was registered.
+    pub fn unregister_tx_listener(&self, key: TxListenerKey) -> bool {
+        self.tx_listeners.lock().unwrap().listeners.remove(&key).is_some()
+    }
+
+    /// Notify interested listeners of a committed transaction.
+    fn notify_tx_listeners(&self, report: &TxReport) {
+        // Collect the listeners first, so that no lock is held while they run; listeners may
+        // transact, query, or (un)register listeners themselves.
+        let listeners: Vec<Arc<TxListener>> = self.tx_listeners.lock().unwrap().listeners.values()
+            .filter(|listener| listener.is_interested_in(report))
+            .cloned()
+            .collect();
+
+        for listener in listeners {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| (listener.callback)(report)));
+        }
+    }
+}
 
-        let (current_generation, current_partition_map, current_schema) =
+/// A read-only view of a Mentat store at a single point in time.  See `Conn::begin_read`.
+pub struct InProgressRead<'a> {
+    transaction: rusqlite::Transaction<'a>,
+    generation: u64,
+    partition_map: PartitionMap,
+    schema: Arc<Schema>,
+}
+
+impl<'a> InProgressRead<'a> {
+    /// The schema of the store as of this view.
+    pub fn schema(&self) -> Arc<Schema> {
+        self.schema.clone()
+    }
+
+    /// Query the store as of this view.
+    pub fn q_once<T, U>(&self,
+                        query: &str,
+                        inputs: T,
+                        limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
         {
-            // The mutex is taken during this block.
-            let ref current: Metadata = *self.metadata.lock().unwrap();
-            (current.generation,
-             // Expensive, but the partition map is updated after every committed transaction.
-             current.partition_map.clone(),
-             // Cheap.
-             current.schema.clone())
-        };
 
-        // The transaction is processed while the mutex is not held.
-        let (report, next_partition_map, next_schema) = transact(&tx, current_partition_map, &*current_schema, entities)?;
+        q_once(&*self.transaction,
+               &*self.schema,
+               query,
+               inputs,
+               limit)
+    }
+}
+
+/// A writable view of a Mentat store.  See `Conn::begin_write`.
+///
+/// Transactions applied to the view are visible to queries against the view, but not elsewhere
+/// until the view is committed.
+pub struct InProgress<'a, 'c> {
+    conn: &'c Conn,
+    transaction: rusqlite::Transaction<'a>,
+    /// The generation of the `Conn` metadata this view began from.
+    generation: u64,
+    /// The partition map and schema as of the last transaction applied to this view.
+    partition_map: PartitionMap,
+    schema: Arc<Schema>,
+    /// The reports of the transactions applied to this view, for listeners to be notified of when
+    /// the view is committed.
+    tx_reports: Vec<TxReport>,
+}
 
+impl<'a, 'c> InProgress<'a, 'c> {
+    /// The schema of the store as of this view.
+    pub fn schema(&self) -> Arc<Schema> {
+        self.schema.clone()
+    }
+
+    /// Query the store as of this view.
+    pub fn q_once<T, U>(&self,
+                        query: &str,
+                        inputs: T,
+                        limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
         {
-            // The mutex is taken during this block.
-            let mut metadata = self.metadata.lock().unwrap();
 
-            if current_generation != metadata.generation {
-                // Somebody else wrote!
-                // Retrying is tracked by https://github.com/mozilla/mentat/issues/357.
-                bail!("Lost the transact() race!");
-            }
+        q_once(&*self.transaction,
+               &*self.schema,
+               query,
+               inputs,
+               limit)
+    }
 
-            // Commit the SQLite transaction while we hold the mutex.
-            tx.commit()?;
+    /// Transact entities against this view.  A transaction that fails leaves the view as it was.
+    pub fn transact(&mut self, transaction: &str) -> Result<TxReport> {
+        let assertion_vector = edn::parse::value(transaction)
+            .map(|x| x.without_spans())?;
+        let entities = mentat_tx_parser::Tx::parse(&[assertion_vector][..])?;
+        self.transact_entities(entities)
+    }
 
-            metadata.generation += 1;
-            metadata.partition_map = next_partition_map;
-            if let Some(next_schema) = next_schema {
-                metadata.schema = Arc::new(next_schema);
-            }
+    /// Revert the transaction `tx` in this view.  See `Conn::revert`.
+    pub fn revert(&mut self, tx: Entid, force: bool) -> Result<TxReport> {
+        let entities = db::revert_entities(&*self.transaction, &*self.schema, tx, force)?;
+        self.transact_entities(entities)
+    }
+
+    fn transact_entities(&mut self, entities: Vec<Entity>) -> Result<TxReport> {
+        // Dropping the savepoint without committing it rolls back any partial writes.
+        let savepoint = self.transaction.savepoint()?;
+
+        let (report, next_partition_map, next_schema) = transact(&savepoint, self.partition_map.clone(), &*self.schema, entities)?;
+
+        savepoint.commit()?;
+
+        self.partition_map = next_partition_map;
+        if let Some(next_schema) = next_schema {
+            self.schema = Arc::new(next_schema);
         }
+        self.tx_reports.push(report.clone());
 
         Ok(report)
     }
+
+    /// Commit this view, advancing the `Conn` metadata -- generation, partition map, and schema --
+    /// in one step, and then notify listeners of each transaction applied to the view.
+    pub fn commit(self) -> Result<()> {
+        {
+            // The mutex is taken during this block.
+            let mut metadata = self.conn.metadata.lock().unwrap();
+
+            // Commit the SQLite transaction while we hold the mutex.  The view has held the SQLite
+            // write lock since it began from the current metadata, so nobody else has written.
+            self.transaction.commit()?;
+
+            metadata.generation += 1;
+            metadata.partition_map = self.partition_map;
+            metadata.schema = self.schema;
+        }
+
+        for report in &self.tx_reports {
+            self.conn.notify_tx_listeners(report);
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use mentat_db::debug::TempPath;
+
     extern crate mentat_parser_utils;
     use self::mentat_parser_utils::ValueParseError;
 
+    use mentat_db::entids::{DB_DOC, DB_IDENT};
+
+    #[test]
+    fn test_connect_reopens_existing_store() {
+        let path = TempPath::new("connect_reopens_existing_store");
+
+        let partition_map = {
+            let mut sqlite = db::new_connection(&path).unwrap();
+            let conn = Conn::connect(&mut sqlite).unwrap();
+            conn.transact(&mut sqlite, "[[:db/add \"t\" :db/ident :a/keyword]]").unwrap();
+            let metadata = conn.metadata.lock().unwrap();
+            metadata.partition_map.clone()
+        };
+
+        let mut sqlite = db::new_connection(&path).unwrap();
+        let conn = Conn::connect(&mut sqlite).unwrap();
+        assert_eq!(conn.metadata.lock().unwrap().partition_map, partition_map);
+
+        // The re-opened store continues allocating from where it left off.
+        let report = conn.transact(&mut sqlite, "[]").unwrap();
+        assert_eq!(report.tx_id, 0x10000000 + 2);
+    }
+
+    #[test]
+    fn test_transact_schema() {
+        let mut sqlite = db::new_connection("").unwrap();
+        let conn = Conn::connect(&mut sqlite).unwrap();
+
+        let schema_before = conn.current_schema();
+        conn.transact(&mut sqlite, r#"[{:db/ident :person/name
+                                         :db/valueType :db.type/string
+                                         :db/cardinality :db.cardinality/one}]"#).unwrap();
+
+        // The new attribute is visible in the next schema, but not in references to the old schema.
+        let ident = edn::NamespacedKeyword::new("person", "name");
+        assert!(schema_before.get_entid(&ident).is_none());
+        assert!(conn.current_schema().get_entid(&ident).is_some());
+
+        // And can be used immediately.
+        conn.transact(&mut sqlite, r#"[[:db/add "p" :person/name "Ivan"]]"#).unwrap();
+    }
+
     #[test]
     fn test_transact_errors() {
         let mut sqlite = db::new_connection("").unwrap();
-        let mut conn = Conn::connect(&mut sqlite).unwrap();
+        let conn = Conn::connect(&mut sqlite).unwrap();
 
         // Good: empty transaction.
         let report = conn.transact(&mut sqlite, "[]").unwrap();
@@ -215,8 +616,188 @@ mod tests {
         let report = conn.transact(&mut sqlite, "[[:db/add \"u\" :db/ident :a/keyword]
                                                   [:db/add \"u\" :db/ident :b/keyword]]");
         match report.unwrap_err() {
-            Error(ErrorKind::DbError(::mentat_db::errors::ErrorKind::NotYetImplemented(_)), _) => { },
-            x => panic!("expected EDN parse error, got {:?}", x),
+            Error(ErrorKind::DbError(::mentat_db::errors::ErrorKind::ConflictingUpsert(_, _)), _) => { },
+            x => panic!("expected conflicting upsert error, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_transact_through_many_conns() {
+        let path = TempPath::new("transact_through_many_conns");
+
+        let mut sqlite1 = db::new_connection(&path).unwrap();
+        let conn1 = Conn::connect(&mut sqlite1).unwrap();
+        let mut sqlite2 = db::new_connection(&path).unwrap();
+        let conn2 = Conn::connect(&mut sqlite2).unwrap();
+
+        let report1 = conn1.transact(&mut sqlite1, r#"[{:db/ident :person/name
+                                                         :db/valueType :db.type/string
+                                                         :db/cardinality :db.cardinality/one}]"#).unwrap();
+
+        // The second `Conn` picks up the first `Conn`'s write before writing itself.
+        let report2 = conn2.transact(&mut sqlite2, r#"[[:db/add "p" :person/name "Ivan"]]"#).unwrap();
+        assert_eq!(report2.tx_id, report1.tx_id + 1);
+        assert!(conn2.current_schema().get_entid(&edn::NamespacedKeyword::new("person", "name")).is_some());
+
+        // And vice versa.
+        let report3 = conn1.transact(&mut sqlite1, r#"[[:db/add "q" :person/name "Petr"]]"#).unwrap();
+        assert_eq!(report3.tx_id, report2.tx_id + 1);
+        assert_eq!(report3.tempids["q"], report2.tempids["p"] + 1);
+    }
+
+    #[test]
+    fn test_tx_listeners() {
+        let mut sqlite = db::new_connection("").unwrap();
+        let conn = Conn::connect(&mut sqlite).unwrap();
+
+        let calls = Arc::new(Mutex::new(vec![]));
+        let doc_calls = calls.clone();
+        let doc_key = conn.register_tx_listener(vec![DB_DOC].into_iter().collect(), move |report| {
+            doc_calls.lock().unwrap().push(report.tx_id);
+        });
+        let (_, ident_reports) = conn.register_tx_channel(vec![DB_IDENT].into_iter().collect());
+
+        // A panicking listener neither fails the transaction nor stops other listeners.
+        conn.register_tx_listener(vec![DB_IDENT, DB_DOC].into_iter().collect(), |_| panic!("listener panicked"));
+
+        let report = conn.transact(&mut sqlite, r#"[[:db/add "t" :db/ident :a/keyword]]"#).unwrap();
+        assert_eq!(ident_reports.try_recv().unwrap(), report);
+        assert!(calls.lock().unwrap().is_empty());
+
+        let report = conn.transact(&mut sqlite, r#"[[:db/add :a/keyword :db/doc "a keyword"]]"#).unwrap();
+        assert!(ident_reports.try_recv().is_err());
+        assert_eq!(*calls.lock().unwrap(), vec![report.tx_id]);
+
+        // The metadata mutex isn't poisoned.
+        assert!(conn.current_schema().get_entid(&edn::NamespacedKeyword::new("a", "keyword")).is_some());
+
+        // Unregistered listeners aren't called.
+        assert!(conn.unregister_tx_listener(doc_key));
+        assert!(!conn.unregister_tx_listener(doc_key));
+        conn.transact(&mut sqlite, r#"[[:db/add :a/keyword :db/doc "still a keyword"]]"#).unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_in_progress() {
+        let mut sqlite = db::new_connection("").unwrap();
+        let conn = Conn::connect(&mut sqlite).unwrap();
+
+        let (_, reports) = conn.register_tx_channel(vec![DB_IDENT].into_iter().collect());
+        let ident = edn::NamespacedKeyword::new("person", "name");
+        let query = r#"[:find ?name . :where [_ :person/name ?name]]"#;
+
+        {
+            let mut in_progress = conn.begin_write(&mut sqlite).unwrap();
+            in_progress.transact(r#"[{:db/ident :person/name
+                                      :db/valueType :db.type/string
+                                      :db/cardinality :db.cardinality/one}]"#).unwrap();
+            in_progress.transact(r#"[[:db/add "p" :person/name "Ivan"]]"#).unwrap();
+
+            // A failed transaction leaves the earlier transactions in place.
+            assert!(in_progress.transact(r#"[[:db/add "p" :person/name 1]]"#).is_err());
+
+            // Queries see the pending writes; the `Conn` doesn't.
+            match in_progress.q_once(query, None, None).unwrap() {
+                QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+                x => panic!("expected scalar result, got {:?}", x),
+            }
+            assert!(in_progress.schema().get_entid(&ident).is_some());
+            assert!(conn.current_schema().get_entid(&ident).is_none());
+            assert!(reports.try_recv().is_err());
+
+            in_progress.commit().unwrap();
+        }
+
+        // Committing advances the metadata in one step and notifies listeners.
+        {
+            let metadata = conn.metadata.lock().unwrap();
+            assert_eq!(metadata.generation, 1);
+            assert_eq!(metadata.partition_map.get(":db.part/user").unwrap().index, 0x10000 + 2);
+            assert!(metadata.schema.get_entid(&ident).is_some());
+        }
+        assert_eq!(reports.try_recv().unwrap().tx_id, 0x10000000 + 1);
+
+        match conn.q_once(&sqlite, query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_in_progress_rolls_back_on_drop() {
+        let mut sqlite = db::new_connection("").unwrap();
+        let conn = Conn::connect(&mut sqlite).unwrap();
+
+        {
+            let mut in_progress = conn.begin_write(&mut sqlite).unwrap();
+            let report = in_progress.transact(r#"[[:db/add "t" :db/ident :a/keyword]]"#).unwrap();
+            assert_eq!(report.tx_id, 0x10000000 + 1);
+        }
+
+        assert_eq!(conn.metadata.lock().unwrap().generation, 0);
+        match conn.q_once(&sqlite, r#"[:find ?e . :where [?e :db/ident :a/keyword]]"#, None, None).unwrap() {
+            QueryResults::Scalar(None) => { },
+            x => panic!("expected no result, got {:?}", x),
         }
+
+        // The rolled back transaction ID is reused.
+        let report = conn.transact(&mut sqlite, r#"[[:db/add "t" :db/ident :a/keyword]]"#).unwrap();
+        assert_eq!(report.tx_id, 0x10000000 + 1);
+    }
+
+    #[test]
+    fn test_transact_from_many_threads() {
+        let path = TempPath::new("transact_from_many_threads");
+
+        let conn = {
+            let mut sqlite = db::new_connection(&path).unwrap();
+            Arc::new(Conn::connect(&mut sqlite).unwrap())
+        };
+
+        let threads: Vec<_> = (0..4).map(|i| {
+            let conn = conn.clone();
+            let path = path.as_ref().to_path_buf();
+            ::std::thread::spawn(move || -> Vec<i64> {
+                let mut sqlite = db::new_connection(&path).unwrap();
+                (0..10).map(|j| {
+                    let transaction = format!("[[:db/add \"t\" :db/ident :test/thread{}-{}]]", i, j);
+                    conn.transact(&mut sqlite, &transaction).unwrap().tx_id
+                }).collect()
+            })
+        }).collect();
+
+        let mut tx_ids: Vec<i64> = threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect();
+        tx_ids.sort();
+        tx_ids.dedup();
+
+        // Every transaction committed, each with its own transaction ID.
+        assert_eq!(tx_ids, (0x10000000 + 1..0x10000000 + 41).collect::<Vec<i64>>());
+
+        let metadata = conn.metadata.lock().unwrap();
+        assert_eq!(metadata.generation, 40);
+        assert_eq!(metadata.partition_map.get(":db.part/user").unwrap().index, 0x10000 + 40);
+    }
+
+    #[test]
+    fn test_transact_race_lost() {
+        let path = TempPath::new("transact_race_lost");
+
+        let mut sqlite1 = db::new_connection(&path).unwrap();
+        let conn1 = Conn::connect(&mut sqlite1).unwrap();
+
+        // Another connection holds the write lock, and we don't wait for it.
+        let sqlite2 = db::new_connection(&path).unwrap();
+        sqlite2.execute_batch("BEGIN IMMEDIATE").unwrap();
+        sqlite1.execute_batch("PRAGMA busy_timeout = 0").unwrap();
+
+        match conn1.transact(&mut sqlite1, "[]").unwrap_err() {
+            Error(ErrorKind::TransactRaceLost(attempts), _) => assert_eq!(attempts, MAX_TRANSACT_ATTEMPTS),
+            x => panic!("expected lost transact() race error, got {:?}", x),
+        }
+
+        // Once the other connection lets go, the transaction goes through.
+        sqlite2.execute_batch("ROLLBACK").unwrap();
+        conn1.transact(&mut sqlite1, "[]").unwrap();
     }
 }
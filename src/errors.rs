@@ -42,5 +42,10 @@ error_chain! {
             description("invalid argument name")
             display("invalid argument name: '{}'", name)
         }
+
+        TransactRaceLost(attempts: usize) {
+            description("lost the transact() race")
+            display("lost the transact() race {} times; giving up", attempts)
+        }
     }
 }
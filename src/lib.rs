@@ -17,6 +17,7 @@ extern crate slog;
 #[macro_use]
 extern crate slog_scope;
 
+extern crate chrono;
 extern crate rusqlite;
 
 extern crate edn;
@@ -28,6 +29,7 @@ extern crate mentat_query_parser;
 extern crate mentat_query_projector;
 extern crate mentat_query_translator;
 extern crate mentat_sql;
+extern crate mentat_tx;
 extern crate mentat_tx_parser;
 
 use rusqlite::Connection;
@@ -36,6 +38,7 @@ pub mod errors;
 pub mod ident;
 pub mod conn;
 pub mod query;
+pub mod store;
 
 pub fn get_name() -> String {
     info!("Called into mentat library"; "fn" => "get_name");
@@ -49,15 +52,28 @@ pub fn get_connection() -> Connection {
 
 pub use mentat_db::{
     new_connection,
+    TxLogEntry,
+    TxRange,
+};
+
+pub use conn::{
+    Conn,
+    InProgress,
+    InProgressRead,
 };
 
 pub use query::{
     NamespacedKeyword,
     PlainSymbol,
     QueryResults,
+    q_as_of,
+    q_history,
     q_once,
+    q_since,
 };
 
+pub use store::Store;
+
 #[cfg(test)]
 mod tests {
     use edn::symbols::Keyword;
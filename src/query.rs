@@ -14,11 +14,17 @@ use rusqlite;
 use rusqlite::types::ToSql;
 
 use mentat_core::{
+    Entid,
     Schema,
     TypedValue,
 };
 
-use mentat_query_algebrizer::algebrize;
+use mentat_query_algebrizer::{
+    AlgebraicQuery,
+    DatomsTable,
+    algebrize,
+    algebrize_with_view,
+};
 
 pub use mentat_query::{
     NamespacedKeyword,
@@ -65,14 +71,66 @@ pub fn q_once<'sqlite, 'schema, 'query, T, U>
     // TODO: validate inputs.
 
     let parsed = parse_find_string(query)?;
-    let mut algebrized = algebrize(schema, parsed);
+    run_algebrized(sqlite, algebrize(schema, parsed), limit.into())
+}
+
+/// Like `q_once`, but run the query against the store as it was immediately after the transaction
+/// `tx` was committed.
+#[allow(unused_variables)]
+pub fn q_as_of<'sqlite, 'schema, 'query, T, U>
+(sqlite: &'sqlite rusqlite::Connection,
+ schema: &'schema Schema,
+ tx: Entid,
+ query: &'query str,
+ inputs: T,
+ limit: U) -> QueryExecutionResult
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+{
+    let parsed = parse_find_string(query)?;
+    run_algebrized(sqlite, algebrize_with_view(schema, parsed, DatomsTable::AsOf(tx)), limit.into())
+}
+
+/// Like `q_once`, but run the query against only those current datoms that were asserted after
+/// the transaction `tx`.
+#[allow(unused_variables)]
+pub fn q_since<'sqlite, 'schema, 'query, T, U>
+(sqlite: &'sqlite rusqlite::Connection,
+ schema: &'schema Schema,
+ tx: Entid,
+ query: &'query str,
+ inputs: T,
+ limit: U) -> QueryExecutionResult
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+{
+    let parsed = parse_find_string(query)?;
+    run_algebrized(sqlite, algebrize_with_view(schema, parsed, DatomsTable::Since(tx)), limit.into())
+}
+
+/// Like `q_once`, but run the query against every assertion and retraction in the transaction
+/// log.  Patterns may bind or constrain the fifth `added` place: `[?e ?a ?v ?tx ?added]`.
+#[allow(unused_variables)]
+pub fn q_history<'sqlite, 'schema, 'query, T, U>
+(sqlite: &'sqlite rusqlite::Connection,
+ schema: &'schema Schema,
+ query: &'query str,
+ inputs: T,
+ limit: U) -> QueryExecutionResult
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+{
+    let parsed = parse_find_string(query)?;
+    run_algebrized(sqlite, algebrize_with_view(schema, parsed, DatomsTable::History), limit.into())
+}
 
+fn run_algebrized(sqlite: &rusqlite::Connection, mut algebrized: AlgebraicQuery, limit: Option<u64>) -> QueryExecutionResult {
     if algebrized.is_known_empty() {
         // We don't need to do any SQL work at all.
         return Ok(QueryResults::empty(&algebrized.find_spec));
     }
 
-    algebrized.apply_limit(limit.into());
+    algebrized.apply_limit(limit);
 
     let select = query_to_select(algebrized);
     let SQLQuery { sql, args } = select.query.to_sql_query()?;
@@ -0,0 +1,550 @@
+// Copyright 2016 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use rusqlite;
+
+use conn::{
+    Conn,
+    InProgress,
+    InProgressRead,
+};
+use errors::*;
+use mentat_core::{
+    Entid,
+    Schema,
+    TypedValue,
+};
+use mentat_db::{
+    new_connection,
+    TxRange,
+    TxReport,
+};
+use query::QueryResults;
+
+/// A Mentat store: a SQLite connection, and the `Conn` that tracks the store's metadata.
+///
+/// This is the main entry point for consumers.  A `Store` owns its SQLite connection, so it is
+/// used from a single thread; open more stores on the same path to use the store from more threads.
+/// Each store reloads its metadata from SQLite when it finds that another store has written.
+pub struct Store {
+    sqlite: rusqlite::Connection,
+    conn: Conn,
+}
+
+impl Store {
+    /// Open the Mentat store at the given `path`, creating it if necessary.  The empty path opens a
+    /// new in-memory store.
+    pub fn open<T>(path: T) -> Result<Store> where T: AsRef<Path> {
+        let mut sqlite = new_connection(path)?;
+        let conn = Conn::connect(&mut sqlite)?;
+        Ok(Store {
+            sqlite: sqlite,
+            conn: conn,
+        })
+    }
+
+    /// The `Conn` tracking this store's metadata.  Use it to register transaction listeners.
+    pub fn conn(&self) -> &Conn {
+        &self.conn
+    }
+
+    /// Yield the current `Schema` instance.
+    pub fn current_schema(&self) -> Arc<Schema> {
+        self.conn.current_schema()
+    }
+
+    /// Query the store.
+    pub fn q_once<T, U>(&self,
+                        query: &str,
+                        inputs: T,
+                        limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        self.conn.q_once(&self.sqlite, query, inputs, limit)
+    }
+
+    /// Query the store as it was immediately after the transaction `tx` was committed.
+    pub fn q_as_of<T, U>(&self,
+                         tx: Entid,
+                         query: &str,
+                         inputs: T,
+                         limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        self.conn.q_as_of(&self.sqlite, tx, query, inputs, limit)
+    }
+
+    /// Query only those current datoms that were asserted after the transaction `tx`.
+    pub fn q_since<T, U>(&self,
+                         tx: Entid,
+                         query: &str,
+                         inputs: T,
+                         limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        self.conn.q_since(&self.sqlite, tx, query, inputs, limit)
+    }
+
+    /// Query every assertion and retraction in the store's transaction log.
+    pub fn q_history<T, U>(&self,
+                           query: &str,
+                           inputs: T,
+                           limit: U) -> Result<QueryResults>
+        where T: Into<Option<HashMap<String, TypedValue>>>,
+              U: Into<Option<u64>>
+        {
+
+        self.conn.q_history(&self.sqlite, query, inputs, limit)
+    }
+
+    /// Read the transactions with IDs in `[start, end)` from the store's transaction log.  If `end`
+    /// is `None`, read through the end of the log.
+    pub fn tx_range<T>(&self, start: Entid, end: T) -> TxRange<Arc<Schema>> where T: Into<Option<Entid>> {
+        self.conn.tx_range(&self.sqlite, start, end)
+    }
+
+    /// Transact entities against the store.
+    pub fn transact(&mut self, transaction: &str) -> Result<TxReport> {
+        self.conn.transact(&mut self.sqlite, transaction)
+    }
+
+    /// Revert the transaction `tx`.  Fails if `tx` changed any :db/noHistory attribute, or if a
+    /// later transaction changed any of the same [e a] pairs, unless `force` is true.
+    pub fn revert(&mut self, tx: Entid, force: bool) -> Result<TxReport> {
+        self.conn.revert(&mut self.sqlite, tx, force)
+    }
+
+    /// Begin a read-only view of the store at a single point in time.
+    pub fn begin_read<'a>(&'a mut self) -> Result<InProgressRead<'a>> {
+        self.conn.begin_read(&mut self.sqlite)
+    }
+
+    /// Begin a writable view of the store, holding the write lock until it is committed or dropped.
+    pub fn begin_write<'a>(&'a mut self) -> Result<InProgress<'a, 'a>> {
+        self.conn.begin_write(&mut self.sqlite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+    use edn;
+    use mentat_core::ValueType;
+    use mentat_db::debug::TempPath;
+
+    #[test]
+    fn test_store() {
+        let mut store = Store::open("").unwrap();
+
+        let report = store.transact(r#"[{:db/ident :person/name
+                                          :db/valueType :db.type/string
+                                          :db/cardinality :db.cardinality/one}]"#).unwrap();
+        assert_eq!(report.tx_id, 0x10000000 + 1);
+
+        store.transact(r#"[[:db/add "p" :person/name "Ivan"]]"#).unwrap();
+
+        match store.q_once(r#"[:find ?name . :where [_ :person/name ?name]]"#, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+
+        let ident = edn::NamespacedKeyword::new("person", "name");
+        assert_eq!(store.current_schema().attribute_for_ident(&ident).unwrap().value_type, ValueType::String);
+    }
+
+    #[test]
+    fn test_instant() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :event/at
+                             :db/valueType :db.type/instant
+                             :db/cardinality :db.cardinality/one}]"#).unwrap();
+        let report = store.transact(r#"[[:db/add "e" :event/at #inst "2017-06-16T00:59:11.257Z"]]"#).unwrap();
+        let e = report.tempids["e"];
+
+        let at = Utc.ymd(2017, 6, 16).and_hms_milli(0, 59, 11, 257);
+        let query = format!(r#"[:find ?at . :where [{} :event/at ?at]]"#, e);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Instant(x))) => assert_eq!(x, at),
+            x => panic!("expected instant result, got {:?}", x),
+        }
+
+        // Instants are query constants, too.
+        match store.q_once(r#"[:find ?e . :where [?e :event/at #inst "2017-06-16T02:59:11.257+02:00"]]"#, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Ref(x))) => assert_eq!(x, e),
+            x => panic!("expected entid result, got {:?}", x),
+        }
+
+        // And :db/txInstant is an instant.
+        let query = format!(r#"[:find ?at . :where [{} :db/txInstant ?at]]"#, report.tx_id);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Instant(x))) => assert_eq!(x, report.tx_instant),
+            x => panic!("expected instant result, got {:?}", x),
+        }
+
+        let ident = edn::NamespacedKeyword::new("db", "txInstant");
+        assert_eq!(store.current_schema().attribute_for_ident(&ident).unwrap().value_type, ValueType::Instant);
+    }
+
+    #[test]
+    fn test_uuid_and_uri() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :page/id
+                             :db/valueType :db.type/uuid
+                             :db/cardinality :db.cardinality/one
+                             :db/unique :db.unique/identity}
+                            {:db/ident :page/url
+                             :db/valueType :db.type/uri
+                             :db/cardinality :db.cardinality/one}]"#).unwrap();
+        let report = store.transact(r#"[{:db/id "p"
+                                          :page/id #uuid "f47ac10b-58cc-4372-a567-0e02b2c3d479"
+                                          :page/url "https://example.com/"}]"#).unwrap();
+        let p = report.tempids["p"];
+
+        // The UUID is stored as 16 bytes, so its textual case doesn't matter.
+        let query = r#"[:find ?p . :where [?p :page/id #uuid "F47AC10B-58CC-4372-A567-0E02B2C3D479"]]"#;
+        match store.q_once(query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Ref(x))) => assert_eq!(x, p),
+            x => panic!("expected entid result, got {:?}", x),
+        }
+
+        let query = format!(r#"[:find ?id . :where [{} :page/id ?id]]"#, p);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Uuid(x))) => assert_eq!(x.hyphenated().to_string(), "f47ac10b-58cc-4372-a567-0e02b2c3d479"),
+            x => panic!("expected UUID result, got {:?}", x),
+        }
+
+        // String constants name URIs when the attribute is known.
+        match store.q_once(r#"[:find ?p . :where [?p :page/url "https://example.com/"]]"#, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Ref(x))) => assert_eq!(x, p),
+            x => panic!("expected entid result, got {:?}", x),
+        }
+
+        let query = format!(r#"[:find ?url . :where [{} :page/url ?url]]"#, p);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Uri(ref x))) => assert_eq!(x, "https://example.com/"),
+            x => panic!("expected URI result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :file/sha
+                             :db/valueType :db.type/bytes
+                             :db/cardinality :db.cardinality/one}]"#).unwrap();
+        let report = store.transact(r#"[[:db/add "f" :file/sha #bytes "3q2+7w=="]]"#).unwrap();
+        let f = report.tempids["f"];
+
+        let query = format!(r#"[:find ?sha . :where [{} :file/sha ?sha]]"#, f);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Bytes(ref x))) => assert_eq!(x, &vec![0xde, 0xad, 0xbe, 0xef]),
+            x => panic!("expected bytes result, got {:?}", x),
+        }
+
+        match store.q_once(r#"[:find ?f . :where [?f :file/sha #bytes "3q2+7w=="]]"#, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Ref(x))) => assert_eq!(x, f),
+            x => panic!("expected entid result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_doubles() {
+        let mut store = Store::open("").unwrap();
+
+        let report = store.transact(r#"[{:db/id "w"
+                                          :db/ident :item/weight
+                                          :db/valueType :db.type/double
+                                          :db/cardinality :db.cardinality/one}
+                                         {:db/ident :item/count
+                                          :db/valueType :db.type/long
+                                          :db/cardinality :db.cardinality/one}]"#).unwrap();
+        let weight = report.tempids["w"];
+        let report = store.transact(r#"[[:db/add "i" :item/weight 5]
+                                        [:db/add "i" :item/count 5]]"#).unwrap();
+        let i = report.tempids["i"];
+
+        // The long is stored as a double, and comes back as one.
+        let query = format!(r#"[:find ?w . :where [{} :item/weight ?w]]"#, i);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Double(x))) => assert_eq!(x.into_inner(), 5.0),
+            x => panic!("expected double result, got {:?}", x),
+        }
+
+        let query = format!(r#"[:find ?c . :where [{} :item/count ?c]]"#, i);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Long(x))) => assert_eq!(x, 5),
+            x => panic!("expected long result, got {:?}", x),
+        }
+
+        // SQLite equates 5.0 with 5, but a double only matches doubles.
+        match store.q_once(r#"[:find [?a ...] :where [_ ?a 5.0]]"#, None, None).unwrap() {
+            QueryResults::Coll(attributes) => assert_eq!(attributes, vec![TypedValue::Ref(weight)]),
+            x => panic!("expected coll result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_begin_read_is_a_snapshot() {
+        let path = TempPath::new("begin_read_is_a_snapshot");
+
+        let mut reader = Store::open(&path).unwrap();
+        let mut writer = Store::open(&path).unwrap();
+
+        let query = r#"[:find ?e . :where [?e :db/ident :a/keyword]]"#;
+
+        let read = reader.begin_read().unwrap();
+        writer.transact(r#"[[:db/add "t" :db/ident :a/keyword]]"#).unwrap();
+
+        // The view doesn't see the write, but a fresh query does.
+        match read.q_once(query, None, None).unwrap() {
+            QueryResults::Scalar(None) => { },
+            x => panic!("expected no result, got {:?}", x),
+        }
+        match writer.q_once(query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Ref(65536))) => { },
+            x => panic!("expected entid 65536, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_begin_write_sees_other_stores() {
+        let path = TempPath::new("begin_write_sees_other_stores");
+
+        let mut first = Store::open(&path).unwrap();
+        let mut second = Store::open(&path).unwrap();
+
+        first.transact(r#"[{:db/ident :person/name
+                            :db/valueType :db.type/string
+                            :db/cardinality :db.cardinality/one}]"#).unwrap();
+        first.transact(r#"[[:db/add "p" :person/name "Ivan"]]"#).unwrap();
+
+        // The second store reloads the schema the first store installed.
+        let write = second.begin_write().unwrap();
+        let ident = edn::NamespacedKeyword::new("person", "name");
+        assert!(write.schema().attribute_for_ident(&ident).is_some());
+        match write.q_once(r#"[:find ?name . :where [_ :person/name ?name]]"#, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+        write.commit().unwrap();
+        assert!(second.current_schema().attribute_for_ident(&ident).is_some());
+    }
+
+    #[test]
+    fn test_as_of_and_since() {
+        let mut store = Store::open("").unwrap();
+
+        let tx1 = store.transact(r#"[{:db/ident :person/name
+                                       :db/valueType :db.type/string
+                                       :db/cardinality :db.cardinality/one}]"#).unwrap().tx_id;
+        let tx2 = store.transact(r#"[[:db/add 65536 :person/name "Ivan"]]"#).unwrap().tx_id;
+        let tx3 = store.transact(r#"[[:db/add 65536 :person/name "Ivanka"]]"#).unwrap().tx_id;
+
+        let query = r#"[:find ?name . :where [65536 :person/name ?name]]"#;
+
+        match store.q_as_of(tx1, query, None, None).unwrap() {
+            QueryResults::Scalar(None) => { },
+            x => panic!("expected no result, got {:?}", x),
+        }
+        match store.q_as_of(tx2, query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+        match store.q_as_of(tx3, query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivanka"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+
+        match store.q_since(tx2, query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivanka"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+        match store.q_since(tx3, query, None, None).unwrap() {
+            QueryResults::Scalar(None) => { },
+            x => panic!("expected no result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :person/name
+                             :db/valueType :db.type/string
+                             :db/cardinality :db.cardinality/one}]"#).unwrap();
+        let tx2 = store.transact(r#"[[:db/add 65536 :person/name "Ivan"]]"#).unwrap().tx_id;
+        let tx3 = store.transact(r#"[[:db/add 65536 :person/name "Ivanka"]]"#).unwrap().tx_id;
+
+        let query = r#"[:find ?tx ?added :where [65536 :person/name "Ivan" ?tx ?added]]"#;
+        match store.q_history(query, None, None).unwrap() {
+            QueryResults::Rel(mut rows) => {
+                rows.sort();
+                assert_eq!(rows, vec![vec![TypedValue::Ref(tx2), TypedValue::Boolean(true)],
+                                      vec![TypedValue::Ref(tx3), TypedValue::Boolean(false)]]);
+            },
+            x => panic!("expected rel result, got {:?}", x),
+        }
+
+        let query = r#"[:find ?v . :where [65536 :person/name ?v _ false]]"#;
+        match store.q_history(query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+
+        // The current store has no retractions.
+        match store.q_once(query, None, None).unwrap() {
+            QueryResults::Scalar(None) => { },
+            x => panic!("expected no result, got {:?}", x),
+        }
+
+        // And every current datom is an assertion.
+        let query = r#"[:find ?v ?added :where [65536 :person/name ?v _ ?added]]"#;
+        match store.q_once(query, None, None).unwrap() {
+            QueryResults::Rel(rows) => {
+                assert_eq!(rows, vec![vec![TypedValue::String("Ivanka".to_string()), TypedValue::Boolean(true)]]);
+            },
+            x => panic!("expected rel result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_tx_range() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :person/name
+                             :db/valueType :db.type/string
+                             :db/cardinality :db.cardinality/one}]"#).unwrap();
+        let tx2 = store.transact(r#"[[:db/add 65536 :person/name "Ivan"]]"#).unwrap();
+        let tx3 = store.transact(r#"[[:db/add 65536 :person/name "Ivanka"]]"#).unwrap();
+
+        let entries: Vec<_> = store.tx_range(tx2.tx_id, None).collect::<::std::result::Result<_, _>>().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let name = edn::NamespacedKeyword::new("person", "name");
+        let tx_instant = edn::NamespacedKeyword::new("db", "txInstant");
+        assert_eq!(entries[1].tx_id, tx3.tx_id);
+        assert_eq!(entries[1].tx_instant, tx3.tx_instant);
+        assert_eq!(entries[1].tx_data,
+                   vec![(65536, name.clone(), TypedValue::String("Ivan".to_string()), false),
+                        (65536, name.clone(), TypedValue::String("Ivanka".to_string()), true),
+                        (tx3.tx_id, tx_instant, TypedValue::Instant(tx3.tx_instant), true)]);
+
+        // The range excludes its end.
+        assert_eq!(store.tx_range(tx2.tx_id, tx3.tx_id).count(), 1);
+    }
+
+    #[test]
+    fn test_revert() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :person/name
+                             :db/valueType :db.type/string
+                             :db/cardinality :db.cardinality/one}
+                            {:db/ident :person/friend
+                             :db/valueType :db.type/ref
+                             :db/cardinality :db.cardinality/many}]"#).unwrap();
+        let report = store.transact(r#"[[:db/add "ivan" :person/name "Ivan"]
+                                        [:db/add "petr" :person/name "Petr"]]"#).unwrap();
+        let ivan = report.tempids["ivan"];
+        let petr = report.tempids["petr"];
+
+        let tx3 = store.transact(&format!(r#"[[:db/add {} :person/name "Ivanka"]
+                                              [:db/add {} :person/friend {}]]"#, ivan, ivan, petr)).unwrap().tx_id;
+
+        let report = store.revert(tx3, false).unwrap();
+
+        let query = format!(r#"[:find ?name . :where [{} :person/name ?name]]"#, ivan);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+
+        let query = format!(r#"[:find ?friend . :where [{} :person/friend ?friend]]"#, ivan);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(None) => { },
+            x => panic!("expected no result, got {:?}", x),
+        }
+
+        // The reverting transaction points back at the reverted transaction.
+        let query = format!(r#"[:find ?tx . :where [?tx :db.revert/tx {}]]"#, tx3);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::Ref(tx))) => assert_eq!(tx, report.tx_id),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+
+        // The revert itself changed [ivan :person/name], so reverting the transaction again
+        // conflicts...
+        match store.revert(tx3, false) {
+            Err(Error(ErrorKind::DbError(::mentat_db::ErrorKind::RevertConflict(tx, _)), _)) => assert_eq!(tx, tx3),
+            x => panic!("expected revert conflict, got {:?}", x),
+        }
+
+        // ... unless forced.
+        store.revert(tx3, true).unwrap();
+        let query = format!(r#"[:find ?name . :where [{} :person/name ?name]]"#, ivan);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_revert_no_history() {
+        let mut store = Store::open("").unwrap();
+
+        store.transact(r#"[{:db/ident :person/name
+                             :db/valueType :db.type/string
+                             :db/cardinality :db.cardinality/one}
+                            {:db/ident :person/mood
+                             :db/valueType :db.type/string
+                             :db/cardinality :db.cardinality/one
+                             :db/noHistory true}]"#).unwrap();
+        let report = store.transact(r#"[[:db/add "ivan" :person/name "Ivan"]
+                                        [:db/add "ivan" :person/mood "happy"]]"#).unwrap();
+        let ivan = report.tempids["ivan"];
+
+        let tx3 = store.transact(&format!(r#"[[:db/add {} :person/name "Ivanka"]
+                                              [:db/add {} :person/mood "sad"]]"#, ivan, ivan)).unwrap().tx_id;
+
+        // The log doesn't remember that Ivan was happy, so reverting fails...
+        match store.revert(tx3, false) {
+            Err(Error(ErrorKind::DbError(::mentat_db::ErrorKind::RevertNoHistory(tx, ref attributes)), _)) => {
+                assert_eq!(tx, tx3);
+                assert_eq!(attributes, &vec![":person/mood".to_string()]);
+            },
+            x => panic!("expected revert no history error, got {:?}", x),
+        }
+
+        // ... unless forced, which reverts what the log remembers.
+        store.revert(tx3, true).unwrap();
+        let query = format!(r#"[:find ?name . :where [{} :person/name ?name]]"#, ivan);
+        match store.q_once(&query, None, None).unwrap() {
+            QueryResults::Scalar(Some(TypedValue::String(ref name))) => assert_eq!(name, "Ivan"),
+            x => panic!("expected scalar result, got {:?}", x),
+        }
+    }
+}
@@ -24,7 +24,7 @@ use combine::{any, eof, many, parser, satisfy_map, token, Parser, ParseResult, S
 use combine::combinator::{Expected, FnParser};
 use edn::symbols::NamespacedKeyword;
 use edn::types::Value;
-use mentat_tx::entities::{Entid, EntidOrLookupRefOrTempId, Entity, LookupRef, OpType};
+use mentat_tx::entities::{Entid, EntidOrLookupRef, EntidOrLookupRefOrTempId, Entity, LookupRef, MapNotation, MapNotationValue, OpType, PartitionedTempId};
 use mentat_parser_utils::{ResultParser, ValueParseError};
 
 pub mod errors;
@@ -70,10 +70,18 @@ def_parser_fn!(Tx, lookup_ref, Value, LookupRef, input, {
         .parse_stream(input)
 });
 
+def_parser_fn!(Tx, entid_or_lookup_ref, Value, EntidOrLookupRef, input, {
+    Tx::<I>::entid().map(|x| EntidOrLookupRef::Entid(x))
+        .or(Tx::<I>::lookup_ref().map(|x| EntidOrLookupRef::LookupRef(x)))
+        .parse_lazy(input)
+        .into()
+});
+
 def_parser_fn!(Tx, entid_or_lookup_ref_or_temp_id, Value, EntidOrLookupRefOrTempId, input, {
     Tx::<I>::entid().map(|x| EntidOrLookupRefOrTempId::Entid(x))
         .or(Tx::<I>::lookup_ref().map(|x| EntidOrLookupRefOrTempId::LookupRef(x)))
         .or(Tx::<I>::temp_id().map(|x| EntidOrLookupRefOrTempId::TempId(x)))
+        .or(Tx::<I>::partitioned_temp_id().map(|x| EntidOrLookupRefOrTempId::PartitionedTempId(x)))
         .parse_lazy(input)
         .into()
 });
@@ -83,6 +91,27 @@ def_parser_fn!(Tx, temp_id, Value, String, input, {
         .parse_stream(input)
 });
 
+/// Parse `(tempid :db.part/x)` or `(tempid :db.part/x "name")`.
+pub fn value_to_partitioned_temp_id(val: &Value) -> Option<PartitionedTempId> {
+    if let Value::List(ref xs) = *val {
+        let mut xs = xs.iter();
+        match (xs.next(), xs.next(), xs.next(), xs.next()) {
+            (Some(&Value::PlainSymbol(ref s)), Some(&Value::NamespacedKeyword(ref part)), name, None) if s.0 == "tempid" => {
+                let name = match name {
+                    None => None,
+                    Some(&Value::Text(ref name)) => Some(name.clone()),
+                    Some(_) => return None,
+                };
+                Some(PartitionedTempId { part: part.clone(), name: name })
+            },
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+def_value_satisfy_parser_fn!(Tx, partitioned_temp_id, PartitionedTempId, value_to_partitioned_temp_id);
+
 // TODO: abstract the "match Vector, parse internal stream" pattern to remove this boilerplate.
 def_parser_fn!(Tx, add, Value, Entity, input, {
     satisfy_map(|x: Value| -> Option<Entity> {
@@ -142,9 +171,98 @@ def_parser_fn!(Tx, retract, Value, Entity, input, {
         .parse_stream(input)
 });
 
+def_parser_fn!(Tx, retract_entity, Value, Entity, input, {
+    satisfy_map(|x: Value| -> Option<Entity> {
+            if let Value::Vector(y) = x {
+                let mut p = (token(Value::NamespacedKeyword(NamespacedKeyword::new("db.fn", "retractEntity"))),
+                             Tx::<&[Value]>::entid_or_lookup_ref(),
+                             eof())
+                    .map(|(_, e, _)| Entity::RetractEntity { e: e });
+                // TODO: use ok() with a type annotation rather than explicit match.
+                match p.parse_lazy(&y[..]).into() {
+                    Ok((r, _)) => Some(r),
+                    _ => None,
+                }
+            } else {
+                None
+            }
+        })
+        .parse_stream(input)
+});
+
+def_parser_fn!(Tx, cas, Value, Entity, input, {
+    satisfy_map(|x: Value| -> Option<Entity> {
+            if let Value::Vector(y) = x {
+                let mut p = (token(Value::NamespacedKeyword(NamespacedKeyword::new("db.fn", "cas"))),
+                             Tx::<&[Value]>::entid_or_lookup_ref(),
+                             Tx::<&[Value]>::entid(),
+                             any(),
+                             any(),
+                             eof())
+                    .map(|(_, e, a, old, new, _)| {
+                        Entity::Cas {
+                            e: e,
+                            a: a,
+                            old: old,
+                            new: new,
+                        }
+                    });
+                // TODO: use ok() with a type annotation rather than explicit match.
+                match p.parse_lazy(&y[..]).into() {
+                    Ok((r, _)) => Some(r),
+                    _ => None,
+                }
+            } else {
+                None
+            }
+        })
+        .parse_stream(input)
+});
+
+fn value_to_map_notation_value(val: Value) -> Option<MapNotationValue> {
+    match val {
+        Value::Vector(vs) => vs.into_iter()
+            .map(value_to_map_notation_value)
+            .collect::<Option<Vec<_>>>()
+            .map(MapNotationValue::Vector),
+        Value::Map(m) => value_to_map_notation(m).map(MapNotationValue::MapNotation),
+        val => match value_to_partitioned_temp_id(&val) {
+            Some(temp_id) => Some(MapNotationValue::PartitionedTempId(temp_id)),
+            None => Some(MapNotationValue::Atom(val)),
+        },
+    }
+}
+
+fn value_to_map_notation(m: ::std::collections::BTreeMap<Value, Value>) -> Option<MapNotation> {
+    m.into_iter()
+        .map(|(k, v)| {
+            let k = match k {
+                Value::Integer(x) => Entid::Entid(x),
+                Value::NamespacedKeyword(x) => Entid::Ident(x),
+                _ => return None,
+            };
+            value_to_map_notation_value(v).map(|v| (k, v))
+        })
+        .collect()
+}
+
+def_parser_fn!(Tx, map_notation, Value, Entity, input, {
+    satisfy_map(|x: Value| -> Option<Entity> {
+            if let Value::Map(m) = x {
+                value_to_map_notation(m).map(Entity::MapNotation)
+            } else {
+                None
+            }
+        })
+        .parse_stream(input)
+});
+
 def_parser_fn!(Tx, entity, Value, Entity, input, {
     let mut p = Tx::<I>::add()
-        .or(Tx::<I>::retract());
+        .or(Tx::<I>::retract())
+        .or(Tx::<I>::retract_entity())
+        .or(Tx::<I>::cas())
+        .or(Tx::<I>::map_notation());
     p.parse_stream(input)
 });
 
@@ -179,9 +297,10 @@ impl<'a> Tx<&'a [edn::Value]> {
 mod tests {
     use super::*;
     use combine::Parser;
-    use edn::symbols::NamespacedKeyword;
+    use edn::symbols::{NamespacedKeyword, PlainSymbol};
     use edn::types::Value;
-    use mentat_tx::entities::{Entid, EntidOrLookupRefOrTempId, Entity, LookupRef, OpType};
+    use mentat_tx::entities::{Entid, EntidOrLookupRef, EntidOrLookupRefOrTempId, Entity, LookupRef, MapNotation, MapNotationValue, OpType, PartitionedTempId};
+    use std::collections::LinkedList;
 
     fn kw(namespace: &str, name: &str) -> Value {
         Value::NamespacedKeyword(NamespacedKeyword::new(namespace, name))
@@ -245,4 +364,118 @@ mod tests {
                    },
                        &[][..])));
     }
+
+    #[test]
+    fn test_partitioned_temp_id() {
+        let temp_id = |name: Option<&str>| -> Value {
+            let mut xs = LinkedList::new();
+            xs.push_back(Value::PlainSymbol(PlainSymbol::new("tempid")));
+            xs.push_back(kw("db.part", "user"));
+            if let Some(name) = name {
+                xs.push_back(Value::Text(name.into()));
+            }
+            Value::List(xs)
+        };
+
+        let input = [Value::Vector(vec![kw("db", "add"),
+                                        temp_id(Some("t")),
+                                        kw("test", "a"),
+                                        temp_id(None)])];
+        let mut parser = Tx::entity();
+        let result = parser.parse(&input[..]);
+        assert_eq!(result,
+                   Ok((Entity::AddOrRetract {
+                       op: OpType::Add,
+                       e: EntidOrLookupRefOrTempId::PartitionedTempId(PartitionedTempId {
+                           part: NamespacedKeyword::new("db.part", "user"),
+                           name: Some("t".into()),
+                       }),
+                       a: Entid::Ident(NamespacedKeyword::new("test", "a")),
+                       v: temp_id(None),
+                   },
+                       &[][..])));
+
+        let mut map = ::std::collections::BTreeMap::new();
+        map.insert(kw("db", "id"), temp_id(None));
+
+        let input = [Value::Map(map)];
+        let mut parser = Tx::entity();
+        let result = parser.parse(&input[..]);
+
+        let mut expected: MapNotation = ::std::collections::BTreeMap::new();
+        expected.insert(Entid::Ident(NamespacedKeyword::new("db", "id")),
+                        MapNotationValue::PartitionedTempId(PartitionedTempId {
+                            part: NamespacedKeyword::new("db.part", "user"),
+                            name: None,
+                        }));
+
+        assert_eq!(result,
+                   Ok((Entity::MapNotation(expected),
+                       &[][..])));
+    }
+
+    #[test]
+    fn test_map_notation() {
+        let mut inner = ::std::collections::BTreeMap::new();
+        inner.insert(kw("test", "b"), Value::Integer(1));
+
+        let mut outer = ::std::collections::BTreeMap::new();
+        outer.insert(kw("db", "id"), Value::Text("t".into()));
+        outer.insert(kw("test", "a"), Value::Vector(vec![Value::Text("v".into()), Value::Map(inner)]));
+
+        let input = [Value::Map(outer)];
+        let mut parser = Tx::entity();
+        let result = parser.parse(&input[..]);
+
+        let mut expected_inner: MapNotation = ::std::collections::BTreeMap::new();
+        expected_inner.insert(Entid::Ident(NamespacedKeyword::new("test", "b")),
+                              MapNotationValue::Atom(Value::Integer(1)));
+
+        let mut expected: MapNotation = ::std::collections::BTreeMap::new();
+        expected.insert(Entid::Ident(NamespacedKeyword::new("db", "id")),
+                        MapNotationValue::Atom(Value::Text("t".into())));
+        expected.insert(Entid::Ident(NamespacedKeyword::new("test", "a")),
+                        MapNotationValue::Vector(vec![MapNotationValue::Atom(Value::Text("v".into())),
+                                                      MapNotationValue::MapNotation(expected_inner)]));
+
+        assert_eq!(result,
+                   Ok((Entity::MapNotation(expected),
+                       &[][..])));
+    }
+
+    #[test]
+    fn test_retract_entity() {
+        let input = [Value::Vector(vec![kw("db.fn", "retractEntity"),
+                                        Value::Vector(vec![kw("test", "a1"),
+                                                           Value::Text("v1".into())])])];
+        let mut parser = Tx::entity();
+        let result = parser.parse(&input[..]);
+        assert_eq!(result,
+                   Ok((Entity::RetractEntity {
+                       e: EntidOrLookupRef::LookupRef(LookupRef {
+                           a: Entid::Ident(NamespacedKeyword::new("test", "a1")),
+                           v: Value::Text("v1".into()),
+                       }),
+                   },
+                       &[][..])));
+    }
+
+    #[test]
+    fn test_cas() {
+        let input = [Value::Vector(vec![kw("db.fn", "cas"),
+                                        Value::Integer(101),
+                                        kw("test", "a"),
+                                        Value::Nil,
+                                        Value::Text("v".into())])];
+        let mut parser = Tx::entity();
+        let result = parser.parse(&input[..]);
+        assert_eq!(result,
+                   Ok((Entity::Cas {
+                       e: EntidOrLookupRef::Entid(Entid::Entid(101)),
+                       a: Entid::Ident(NamespacedKeyword::new("test", "a")),
+                       old: Value::Nil,
+                       new: Value::Text("v".into()),
+                   },
+                       &[][..])));
+    }
 }
@@ -12,6 +12,8 @@
 
 extern crate edn;
 
+use std::collections::BTreeMap;
+
 use self::edn::types::Value;
 use self::edn::symbols::NamespacedKeyword;
 
@@ -34,11 +36,20 @@ pub enum EntidOrLookupRef {
     LookupRef(LookupRef),
 }
 
+/// A tempid allocated from a named partition, like `(tempid :db.part/myapp "t")`.  Without a name,
+/// like `(tempid :db.part/myapp)`, each occurrence is a distinct tempid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartitionedTempId {
+    pub part: NamespacedKeyword,
+    pub name: Option<String>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum EntidOrLookupRefOrTempId {
     Entid(Entid),
     LookupRef(LookupRef),
     TempId(String),
+    PartitionedTempId(PartitionedTempId),
 }
 
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
@@ -47,6 +58,23 @@ pub enum OpType {
     Retract,
 }
 
+/// A value in map notation: a single value, a vector of values, or a nested map.
+///
+/// The parser doesn't know the schema, so it can't tell whether a vector is a lookup-ref or a
+/// collection of values for a :db.cardinality/many attribute; the transactor decides.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapNotationValue {
+    Atom(Value),
+    Vector(Vec<MapNotationValue>),
+    MapNotation(MapNotation),
+    /// `(tempid :db.part/x "n")`, naming an entity in a particular partition.
+    PartitionedTempId(PartitionedTempId),
+}
+
+/// An entity in map notation, like `{:db/id "t" :person/name "x"}`.  Keys are attributes,
+/// possibly reversed (like `:person/_friends`), or `:db/id`.
+pub type MapNotation = BTreeMap<Entid, MapNotationValue>;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Entity {
     AddOrRetract {
@@ -55,4 +83,18 @@ pub enum Entity {
         a: Entid,
         v: Value,
     },
+    MapNotation(MapNotation),
+    /// `[:db.fn/retractEntity e]`: retract every datom with entity `e` or ref value `e`, recursing
+    /// into the entities `e` refers to through :db/isComponent attributes.
+    RetractEntity {
+        e: EntidOrLookupRef,
+    },
+    /// `[:db.fn/cas e a old new]`: assert `new` for attribute `a` of entity `e`, provided the current
+    /// value is `old`.  An `old` of `nil` means that there is no current value.
+    Cas {
+        e: EntidOrLookupRef,
+        a: Entid,
+        old: Value,
+        new: Value,
+    },
 }